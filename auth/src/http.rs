@@ -0,0 +1,209 @@
+//! HTTP client used to talk to identity providers. Discovery documents and
+//! key sets are cached, they rarely change.
+
+use error::{SBError, SBResult};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest response accepted, in bytes.
+const MAX_RESPONSE_SIZE: usize = 1024 * 1024;
+/// How long cached documents are used.
+const DOCUMENT_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+fn http_error(message: &str) -> SBError {
+    SBError::InternalServiceError {
+        service: String::from("http"),
+        message: String::from(message),
+    }
+}
+
+/// JSON documents fetched by URL, kept for `ttl`.
+#[derive(Clone)]
+struct DocumentCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, Value)>>>,
+}
+
+impl DocumentCache {
+    fn new(ttl: Duration) -> DocumentCache {
+        DocumentCache {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    fn get(&self, url: &str) -> Option<Value> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(url)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < self.ttl)
+            .map(|(_, document)| document.clone())
+    }
+
+    fn insert(&self, url: &str, document: Value) {
+        if let Ok(mut entries) = self.entries.lock() {
+            let ttl = self.ttl;
+            entries.retain(|_, (fetched_at, _)| fetched_at.elapsed() < ttl);
+            entries.insert(String::from(url), (Instant::now(), document));
+        }
+    }
+}
+
+/// Client of the identity providers. Clones share their connections and
+/// cached documents.
+#[derive(Clone)]
+pub struct Client {
+    client: reqwest::Client,
+    documents: DocumentCache,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+impl Client {
+    pub fn new() -> Client {
+        Client {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .user_agent("snellbaas")
+                .build()
+                .expect("Failure building HTTP client"),
+            documents: DocumentCache::new(DOCUMENT_TTL),
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> SBResult<Response> {
+        let mut response = request
+            .header(reqwest::header::ACCEPT, "application/json")
+            .send()
+            .await
+            .map_err(|_| http_error("Failure sending HTTP request."))?;
+        let status = response.status().as_u16();
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|_| http_error("Failure reading HTTP response."))?
+        {
+            if body.len() + chunk.len() > MAX_RESPONSE_SIZE {
+                return Err(http_error("HTTP response too large."));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(Response { status, body })
+    }
+
+    pub async fn get(&self, url: &str, headers: &[(&str, &str)]) -> SBResult<Response> {
+        let mut request = self.client.get(url);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        self.send(request).await
+    }
+
+    pub async fn post_form(&self, url: &str, form: &[(&str, &str)]) -> SBResult<Response> {
+        self.send(self.client.post(url).form(form)).await
+    }
+
+    /// JSON document at `url`, from the cache unless `refresh` is set.
+    /// Only successful responses are cached.
+    pub async fn get_cached_json(&self, url: &str, refresh: bool) -> SBResult<Option<Value>> {
+        if !refresh {
+            if let Some(document) = self.documents.get(url) {
+                return Ok(Some(document));
+            }
+        }
+        let response = self.get(url, &[]).await?;
+        if !response.is_success() {
+            return Ok(None);
+        }
+        let document: Value = match serde_json::from_slice(&response.body) {
+            Ok(document) => document,
+            Err(_) => return Ok(None),
+        };
+        self.documents.insert(url, document.clone());
+        Ok(Some(document))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_document_cache() {
+        let cache = DocumentCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get("https://idp/jwks"), None);
+        cache.insert("https://idp/jwks", json!({"keys": []}));
+        assert_eq!(
+            cache.clone().get("https://idp/jwks"),
+            Some(json!({"keys": []}))
+        );
+
+        let expired = DocumentCache::new(Duration::from_secs(0));
+        expired.insert("https://idp/jwks", json!({}));
+        assert_eq!(expired.get("https://idp/jwks"), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_cached_json() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("[::1]:0").await.unwrap();
+        let url = format!("http://{}/jwks", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let served = requests.clone();
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                served.fetch_add(1, Ordering::SeqCst);
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request).await;
+                let body = r#"{"keys":[]}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client = Client::new();
+        let jwks = json!({"keys": []});
+        assert_eq!(
+            client.get_cached_json(&url, false).await.unwrap(),
+            Some(jwks.clone())
+        );
+        assert_eq!(
+            client.clone().get_cached_json(&url, false).await.unwrap(),
+            Some(jwks.clone())
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            client.get_cached_json(&url, true).await.unwrap(),
+            Some(jwks)
+        );
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod http;
+pub mod mailer;
 pub mod models;
 pub mod services;
 
@@ -6,6 +8,9 @@ mod tests {
     use super::*;
     use error::SBError;
     use mongodb::{Client, Database};
+    use services::keys::KeyStore;
+    use services::password_hashing::PasswordHashing;
+    use services::password_policy::PasswordPolicy;
     use services::AuthenticationService;
 
     const DB_NAME: &str = "auth_test";
@@ -22,9 +27,14 @@ mod tests {
 
     #[tokio::test]
     async fn test_connection() {
-        let db = get_db(DB_NAME.to_owned(), DB_URL.to_owned()).await;
+        let db = get_db(DB_URL.to_owned(), DB_NAME.to_owned()).await;
         assert!(db.is_ok());
-        let service =
-            AuthenticationService::init(db.unwrap(), COLLECTION_NAME.to_owned(), SECRET.to_owned());
+        let _service = AuthenticationService::init(
+            db.unwrap(),
+            COLLECTION_NAME.to_owned(),
+            KeyStore::from_secret(SECRET),
+            PasswordPolicy::default(),
+            PasswordHashing::default(),
+        );
     }
 }
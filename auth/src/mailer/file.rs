@@ -0,0 +1,50 @@
+use super::{Mail, Mailer};
+use async_trait::async_trait;
+use error::{SBError, SBResult};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+
+/// Mailer for local development, appending the mails to a file or printing
+/// them when no file is given.
+#[derive(Clone, Default)]
+pub struct FileMailer {
+    path: Option<String>,
+}
+
+impl FileMailer {
+    pub fn new(path: Option<String>) -> FileMailer {
+        FileMailer { path }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, mail: Mail) -> SBResult<()> {
+        let content = format!(
+            "To: {}\nSubject: {}\n\n{}\n---\n",
+            mail.to, mail.subject, mail.body
+        );
+        let path = match &self.path {
+            Some(path) => path,
+            None => {
+                println!("{}", content);
+                return Ok(());
+            }
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mailer"),
+                message: String::from("Failure opening mail file."),
+            })?;
+        file.write_all(content.as_bytes())
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mailer"),
+                message: String::from("Failure writing mail."),
+            })
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use error::SBResult;
+
+pub mod file;
+pub mod smtp;
+
+pub use file::FileMailer;
+pub use smtp::SmtpMailer;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers the mails sent by the authentication flows.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> SBResult<()>;
+}
+
+fn with_link(text: &str, token: &str, link: Option<&str>) -> String {
+    match link {
+        Some(link) => format!("{}\n\n{}?token={}\n", text, link, token),
+        None => format!("{}\n\nToken: {}\n", text, token),
+    }
+}
+
+pub fn email_verification_mail(to: &str, token: &str, link: Option<&str>) -> Mail {
+    Mail {
+        to: String::from(to),
+        subject: String::from("Verify your email address"),
+        body: with_link(
+            "Confirm your email address with the following token, it expires in 24 hours.",
+            token,
+            link,
+        ),
+    }
+}
+
+pub fn password_reset_mail(to: &str, token: &str, link: Option<&str>) -> Mail {
+    Mail {
+        to: String::from(to),
+        subject: String::from("Reset your password"),
+        body: with_link(
+            "Someone asked to reset your password, ignore this mail if it was not you. \
+             The following token expires in 1 hour.",
+            token,
+            link,
+        ),
+    }
+}
+
+pub fn magic_link_mail(to: &str, token: &str, link: Option<&str>) -> Mail {
+    Mail {
+        to: String::from(to),
+        subject: String::from("Your sign in link"),
+        body: with_link(
+            "Sign in with the following token, ignore this mail if you did not ask for it. \
+             It expires in 15 minutes and works once.",
+            token,
+            link,
+        ),
+    }
+}
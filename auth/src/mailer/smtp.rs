@@ -0,0 +1,185 @@
+use super::{Mail, Mailer};
+use async_trait::async_trait;
+use error::{SBError, SBResult};
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+/// SMTP mailer. With `tls` the connection uses implicit TLS (usually port
+/// 465), otherwise it is sent in clear to a local relay.
+#[derive(Clone)]
+pub struct SmtpMailer {
+    pub host: String,
+    pub port: u16,
+    pub tls: bool,
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+}
+
+fn smtp_error(message: &str) -> SBError {
+    SBError::InternalServiceError {
+        service: String::from("mailer"),
+        message: String::from(message),
+    }
+}
+
+fn invalid_mail(message: &str) -> SBError {
+    SBError::ServiceError {
+        service: String::from("mailer"),
+        message: String::from(message),
+    }
+}
+
+/// Plain text message of a mail. Addresses are parsed so they cannot carry
+/// other headers, the subject is encoded when needed.
+fn build_message(from: &str, mail: &Mail) -> SBResult<Message> {
+    Message::builder()
+        .from(from.parse().map_err(|_| invalid_mail("Invalid sender."))?)
+        .to(mail
+            .to
+            .parse()
+            .map_err(|_| invalid_mail("Invalid recipient."))?)
+        .subject(mail.subject.as_str())
+        .header(ContentType::TEXT_PLAIN)
+        .body(mail.body.clone())
+        .map_err(|_| invalid_mail("Invalid mail."))
+}
+
+impl SmtpMailer {
+    fn transport(&self) -> SBResult<AsyncSmtpTransport<Tokio1Executor>> {
+        let builder = match self.tls {
+            true => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.host)
+                .map_err(|_| smtp_error("Invalid SMTP host."))?,
+            false => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.host),
+        };
+        let builder = builder.port(self.port);
+        let builder = match &self.credentials {
+            Some((username, password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            None => builder,
+        };
+        Ok(builder.build())
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, mail: Mail) -> SBResult<()> {
+        let message = build_message(&self.from, &mail)?;
+        self.transport()?
+            .send(message)
+            .await
+            .map(|_| ())
+            .map_err(|_| smtp_error("Failure sending mail."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_mail(to: &str, subject: &str, body: &str) -> Mail {
+        Mail {
+            to: String::from(to),
+            subject: String::from(subject),
+            body: String::from(body),
+        }
+    }
+
+    #[test]
+    fn test_build_message() {
+        let message = build_message(
+            "SnellBaas <noreply@example.com>",
+            &get_mail("user@example.com", "Reset your password", "a\n.b\n"),
+        )
+        .unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        assert!(formatted.contains("To: user@example.com\r\n"));
+        assert!(formatted.contains("Subject: Reset your password\r\n"));
+        assert_eq!(message.envelope().to()[0].to_string(), "user@example.com");
+
+        assert!(build_message(
+            "noreply@example.com",
+            &get_mail("user@example.com\r\nBcc: other@example.com", "a", "b"),
+        )
+        .is_err());
+        assert!(build_message(
+            "noreply@example.com",
+            &get_mail("user@example.com>\r\nRCPT TO:<other@example.com", "a", "b"),
+        )
+        .is_err());
+
+        let injected = build_message(
+            "noreply@example.com",
+            &get_mail("user@example.com", "a\r\nBcc: other@example.com", "b"),
+        )
+        .unwrap();
+        let formatted = String::from_utf8(injected.formatted()).unwrap();
+        assert!(!formatted.contains("\r\nBcc:"));
+        assert_eq!(injected.envelope().to().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_smtp_exchange() {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut commands = vec![];
+            let mut data = String::new();
+            stream.get_mut().write_all(b"220 test\r\n").await.unwrap();
+            loop {
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let reply: &[u8] = match line.split([' ', ':', '\r']).next().unwrap() {
+                    "EHLO" => b"250-test\r\n250 AUTH PLAIN\r\n",
+                    "AUTH" => b"235 ok\r\n",
+                    "MAIL" | "RCPT" => b"250 ok\r\n",
+                    "DATA" => {
+                        stream.get_mut().write_all(b"354 go\r\n").await.unwrap();
+                        loop {
+                            let mut line = String::new();
+                            stream.read_line(&mut line).await.unwrap();
+                            if line == ".\r\n" {
+                                break;
+                            }
+                            data.push_str(&line);
+                        }
+                        b"250 ok\r\n"
+                    }
+                    "QUIT" => {
+                        stream.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                        break;
+                    }
+                    _ => b"500 unknown\r\n",
+                };
+                commands.push(line);
+                stream.get_mut().write_all(reply).await.unwrap();
+            }
+            (commands, data)
+        });
+
+        let mailer = SmtpMailer {
+            host: String::from("127.0.0.1"),
+            port,
+            tls: false,
+            credentials: Some((String::from("user"), String::from("secret"))),
+            from: String::from("noreply@example.com"),
+        };
+        mailer
+            .send(get_mail("user@example.com", "Hello", "a\n.b\n"))
+            .await
+            .unwrap();
+        let (commands, data) = server.await.unwrap();
+        assert!(commands[1].starts_with("AUTH PLAIN "));
+        assert_eq!(commands[2], "MAIL FROM:<noreply@example.com>\r\n");
+        assert_eq!(commands[3], "RCPT TO:<user@example.com>\r\n");
+        assert!(data.contains("\r\n..b\r\n"));
+    }
+}
@@ -0,0 +1,20 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Failed logins of an account (`email:` keys) or a client (`ip:` keys).
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginAttempts {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub key: String,
+    pub failures: i64,
+    pub last_failure_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locked_until: Option<DateTime>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct UnlockRequest {
+    pub email: String,
+}
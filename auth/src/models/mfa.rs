@@ -0,0 +1,49 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// TOTP enrolment of a user, enforced at login once confirmed.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaEnrolment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub secret: String,
+    #[serde(default)]
+    pub confirmed: bool,
+    #[serde(default)]
+    pub recovery_code_hashes: Vec<String>,
+    #[serde(default)]
+    pub last_used_step: i64,
+    pub created_at: DateTime,
+}
+
+/// Claims of the token returned by a password login when the second factor
+/// is still missing. It has no session so it is not an access token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MfaChallengeClaims {
+    pub sub: String,
+    pub exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    pub mfa_challenge: bool,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaEnrolmentResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct MfaCodeRequest {
+    pub code: String,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MfaVerifyRequest {
+    pub mfa_token: String,
+    pub code: String,
+}
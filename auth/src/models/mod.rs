@@ -1 +1,6 @@
+pub mod login_attempts;
+pub mod mfa;
+pub mod oidc;
+pub mod sessions;
+pub mod tokens;
 pub mod users;
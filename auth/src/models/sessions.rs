@@ -0,0 +1,149 @@
+use actix_web::{dev, http::header, web, Error, FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Longest user agent kept on a session.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+/// A login session, which is also the family of the refresh tokens rotated
+/// from the login. Presenting a rotated-out token revokes the whole family.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub refresh_token_hash: String,
+    #[serde(default)]
+    pub used_token_hashes: Vec<String>,
+    #[serde(default)]
+    pub revoked: bool,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+    /// Last refresh or authenticated request, at a one minute resolution.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen_at: Option<DateTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+}
+
+/// Session as shown to its user, without the refresh token hashes.
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub created_at: DateTime,
+    pub last_seen_at: DateTime,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+    /// Whether the request was made with this session.
+    pub current: bool,
+}
+
+impl SessionInfo {
+    pub fn from_session(session: Session, current_session_id: &str) -> SessionInfo {
+        let id = session.id.map(|id| id.to_hex()).unwrap_or_default();
+        SessionInfo {
+            current: id == current_session_id,
+            id,
+            created_at: session.created_at,
+            last_seen_at: session.last_seen_at.unwrap_or(session.created_at),
+            user_agent: session.user_agent,
+            ip: session.ip,
+        }
+    }
+}
+
+/// Header in which a trusted reverse proxy passes the client address, like
+/// `X-Forwarded-For` or `X-Real-IP`. Without it the peer address is used,
+/// which is the proxy's own when the server sits behind one.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxy {
+    pub header: Option<header::HeaderName>,
+}
+
+/// Address of the client of a request. With a `TrustedProxy` the last
+/// entry of its header is taken, the one added by the proxy, as clients
+/// can send the header with addresses of their choosing.
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+    let proxy_header = req
+        .app_data::<web::Data<TrustedProxy>>()
+        .and_then(|proxy| proxy.header.as_ref());
+    match proxy_header {
+        Some(proxy_header) => req
+            .headers()
+            .get_all(proxy_header)
+            .last()
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty()),
+        None => req.peer_addr().map(|addr| addr.ip().to_string()),
+    }
+}
+
+/// Client opening a session, from the request.
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl FromRequest for ClientInfo {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        let user_agent = req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+        ready(Ok(ClientInfo {
+            ip: client_ip(req),
+            user_agent,
+        }))
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+
+    #[test]
+    fn test_client_ip() {
+        let peer = "10.0.0.1:4000".parse().unwrap();
+        let req = TestRequest::default()
+            .peer_addr(peer)
+            .insert_header(("X-Forwarded-For", "1.1.1.1"))
+            .to_http_request();
+        assert_eq!(client_ip(&req), Some(String::from("10.0.0.1")));
+
+        let proxy = web::Data::new(TrustedProxy {
+            header: Some(header::HeaderName::from_static("x-forwarded-for")),
+        });
+        let req = TestRequest::default()
+            .peer_addr(peer)
+            .app_data(proxy.clone())
+            .insert_header(("X-Forwarded-For", "1.1.1.1, 2.2.2.2"))
+            .to_http_request();
+        assert_eq!(client_ip(&req), Some(String::from("2.2.2.2")));
+
+        let req = TestRequest::default()
+            .peer_addr(peer)
+            .app_data(proxy)
+            .to_http_request();
+        assert_eq!(client_ip(&req), None);
+    }
+}
@@ -0,0 +1,48 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionTokenKind {
+    EmailVerification,
+    PasswordReset,
+    MagicLink,
+}
+
+/// Single use token mailed to a user, only its hash is stored. `email` is
+/// the address the token was sent to.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionToken {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    pub id: Option<ObjectId>,
+    pub user_id: String,
+    pub email: String,
+    pub kind: ActionTokenKind,
+    pub token_hash: String,
+    pub created_at: DateTime,
+    pub expires_at: DateTime,
+}
+
+#[derive(Deserialize, Debug, Serialize, Validate)]
+pub struct EmailRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct MagicLinkRequest {
+    pub token: String,
+}
@@ -1,21 +1,31 @@
 extern crate regex;
 
+use crate::models::oidc::Identity;
 use crate::services::AuthenticationService;
 use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
 use actix_web::{dev, web, Error, FromRequest, HttpRequest};
-use futures::future::{err, ok, Ready};
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
-use mongodb::bson::doc;
+use chrono::prelude::*;
+use error::SBError;
+use futures::Future;
+use mongodb::bson::{self, doc, Document};
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
 use validator::Validate;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+    /// App metadata keys selected as custom claims.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_metadata: Option<Document>,
 }
 
-#[derive(Deserialize, Debug, Serialize, Validate, Clone)]
+#[derive(Deserialize, Debug, Serialize, Validate, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct User {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
@@ -28,28 +38,104 @@ pub struct User {
     pub last_name: String,
     #[validate(email)]
     pub email: String,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub identities: Vec<Identity>,
+    /// Guest without credentials until upgraded, its email is empty.
+    #[serde(default)]
+    pub anonymous: bool,
+    /// Only writable by admins, selected keys are embedded in the tokens.
+    #[serde(default)]
+    pub app_metadata: Document,
+    /// Writable by the user.
+    #[serde(default)]
+    pub user_metadata: Document,
+    #[serde(default)]
+    pub status: AccountStatus,
+    /// Set by an admin, password logins are refused until the password is
+    /// reset.
+    #[serde(default)]
+    pub password_reset_required: bool,
 }
 
 impl User {
     pub fn copy_with_hash(&self, hash: String) -> User {
         User {
-            id: self.id,
-            username: self.username.clone(),
             password: Some(hash),
-            last_name: self.last_name.clone(),
-            first_name: self.first_name.clone(),
-            email: self.email.clone(),
+            ..self.clone()
         }
     }
 
     pub fn copy_without_hash(&self) -> User {
         User {
-            id: self.id,
-            username: self.username.clone(),
             password: None,
-            last_name: self.last_name.clone(),
-            first_name: self.first_name.clone(),
-            email: self.email.clone(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Whether a user may sign in, checked at login and, in strict mode, on
+/// every request.
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq, Default)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Disabled,
+    /// Active again once `until` is past.
+    Banned {
+        until: bson::DateTime,
+    },
+}
+
+impl AccountStatus {
+    pub fn is_active_at(&self, now: bson::DateTime) -> bool {
+        match self {
+            AccountStatus::Active => true,
+            AccountStatus::Disabled => false,
+            AccountStatus::Banned { until } => *until <= now,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountState {
+    Active,
+    Disabled,
+    Banned,
+}
+
+/// Status set by an admin, `until` is the RFC 3339 end of a ban.
+#[derive(Deserialize, Debug, Serialize)]
+pub struct AccountStatusRequest {
+    pub state: AccountState,
+    pub until: Option<String>,
+}
+
+impl AccountStatusRequest {
+    pub fn to_status(&self) -> Result<AccountStatus, SBError> {
+        let invalid_until = || SBError::ServiceError {
+            service: String::from("users"),
+            message: String::from("A ban needs a future RFC 3339 end date."),
+        };
+        match self.state {
+            AccountState::Active => Ok(AccountStatus::Active),
+            AccountState::Disabled => Ok(AccountStatus::Disabled),
+            AccountState::Banned => {
+                let until = self
+                    .until
+                    .as_deref()
+                    .and_then(|until| DateTime::parse_from_rfc3339(until).ok())
+                    .ok_or_else(invalid_until)?;
+                if until.timestamp_millis() <= Utc::now().timestamp_millis() {
+                    return Err(invalid_until());
+                }
+                Ok(AccountStatus::Banned {
+                    until: bson::DateTime::from_millis(until.timestamp_millis()),
+                })
+            }
         }
     }
 }
@@ -65,52 +151,117 @@ pub struct UpdateUser {
     pub last_name: Option<String>,
 }
 
+/// Changes made by a project admin.
+#[derive(Deserialize, Debug, Serialize, Validate)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminUpdateUser {
+    #[validate(length(min = 3))]
+    pub username: Option<String>,
+    #[validate(email)]
+    pub email: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub email_verified: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSearchQuery {
+    pub query: Option<String>,
+    #[serde(default)]
+    pub page: u64,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPage {
+    pub users: Vec<User>,
+    pub total: u64,
+    pub page: u64,
+    pub per_page: i64,
+}
+
+/// Credentials given to an anonymous user.
+#[derive(Deserialize, Debug, Serialize, Validate)]
+pub struct UpgradeUser {
+    #[validate(length(min = 3))]
+    pub username: Option<String>,
+    #[validate(email)]
+    pub email: String,
+    pub password: String,
+}
+
 #[derive(Deserialize, Debug, Serialize, Validate)]
 pub struct AuthorizedUser {
     pub token: String,
     pub sub: String,
+    pub sid: String,
 }
 
 impl FromRequest for AuthorizedUser {
     type Error = Error;
-    type Future = Ready<Result<Self, Self::Error>>;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
     type Config = ();
 
     fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
-        match req.headers().get("Authorization") {
-            Some(val) => match val.to_str() {
-                Ok(v) => {
-                    let my_slice: Vec<&str> = v.split(" ").collect();
-                    let service = match req.app_data::<web::Data<AuthenticationService>>() {
-                        Some(service) => service,
-                        None => {
-                            return err(ErrorInternalServerError("Authentication not configured"))
+        let req_clone = req.clone();
+        Box::pin(async move {
+            match req_clone.headers().get("Authorization") {
+                Some(val) => match val.to_str() {
+                    Ok(v) => {
+                        let my_slice: Vec<&str> = v.split(" ").collect();
+                        let service = match req_clone.app_data::<web::Data<AuthenticationService>>()
+                        {
+                            Some(service) => service,
+                            None => {
+                                return Err(ErrorInternalServerError(
+                                    "Authentication not configured",
+                                ))
+                            }
+                        };
+                        if my_slice.len() != 2 {
+                            return Err(ErrorBadRequest("Bad Headers"));
                         }
-                    };
-                    let key = DecodingKey::from_secret(service.secret.as_ref());
 
-                    if my_slice.len() != 2 {
-                        return err(ErrorBadRequest("Bad Headers"));
-                    }
+                        let token_claims_res = service.keys.decode::<Claims>(my_slice[1], None);
 
-                    let token_claims_res =
-                        decode::<Claims>(my_slice[1], &key, &Validation::new(Algorithm::HS256));
+                        if token_claims_res.is_err() {
+                            return Err(ErrorBadRequest("Not Authorized"));
+                        }
 
-                    if token_claims_res.is_err() {
-                        return err(ErrorBadRequest("Not Authorized"));
+                        let claims = token_claims_res.unwrap();
+                        if claims.aud.is_some() {
+                            return Err(ErrorBadRequest("Not Authorized"));
+                        }
+                        let sid = match claims.sid {
+                            Some(sid) => sid,
+                            None => return Err(ErrorBadRequest("Not Authorized")),
+                        };
+                        match service.sessions.is_active(&sid).await {
+                            Ok(true) => (),
+                            _ => return Err(ErrorBadRequest("Session revoked")),
+                        }
+                        if let Err(error) = service.sessions.touch(&sid).await {
+                            println!("{}", error);
+                        }
+                        if service.status_cache.is_some() {
+                            match service.is_user_active(&claims.sub).await {
+                                Ok(true) => (),
+                                _ => return Err(ErrorBadRequest("Account disabled")),
+                            }
+                        }
+                        Ok(AuthorizedUser {
+                            token: v.into(),
+                            sub: claims.sub,
+                            sid,
+                        })
                     }
-
-                    let claims = token_claims_res.unwrap().claims;
-                    let authorized_user = AuthorizedUser {
-                        token: v.into(),
-                        sub: claims.sub,
-                    };
-                    ok(authorized_user)
-                }
-                Err(e) => err(ErrorBadRequest(e)),
-            },
-            None => err(ErrorBadRequest("Not Authorized")),
-        }
+                    Err(e) => Err(ErrorBadRequest(e)),
+                },
+                None => Err(ErrorBadRequest("Not Authorized")),
+            }
+        })
     }
 }
 
@@ -121,6 +272,13 @@ pub struct AuthenticateUser {
     pub password: String,
 }
 
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
 #[derive(Deserialize)]
 pub struct Info {
     pub user_id: String,
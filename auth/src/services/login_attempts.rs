@@ -0,0 +1,220 @@
+use crate::models::login_attempts::LoginAttempts;
+use error::SBError;
+use mongodb::bson::{doc, DateTime, Document};
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument, UpdateModifications};
+use mongodb::Collection;
+
+type LoginAttemptServiceResult<T> = std::result::Result<T, SBError>;
+
+/// Failures allowed before the next attempts are delayed, accounts are
+/// stricter than clients which may be shared behind a NAT.
+pub const ACCOUNT_FREE_ATTEMPTS: i64 = 5;
+pub const CLIENT_FREE_ATTEMPTS: i64 = 20;
+/// Longest lockout, in milliseconds.
+const MAX_LOCKOUT: i64 = 15 * 60 * 1000;
+/// Failures older than this are forgotten, in milliseconds.
+const FAILURE_WINDOW: i64 = 24 * 3600 * 1000;
+
+/// Lockout after a failure, doubling from one second with every failure
+/// past the free attempts.
+pub fn lockout_duration(failures: i64, free_attempts: i64) -> Option<i64> {
+    if failures < free_attempts {
+        return None;
+    }
+    let exponent = (failures - free_attempts).min(20) as u32;
+    Some((1000_i64 << exponent).min(MAX_LOCKOUT))
+}
+
+pub fn account_key(email: &str) -> String {
+    format!("email:{}", email.to_lowercase())
+}
+
+pub fn client_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+/// Update counting an attempt unless the key is locked at `now`. Failures
+/// older than `FAILURE_WINDOW` are forgotten first, and the lock of
+/// `lockout_duration` only ever extends an existing one.
+fn reserve_pipeline(now: DateTime, free_attempts: i64) -> Vec<Document> {
+    let now_millis = now.timestamp_millis();
+    let locked = doc! {"$gt": ["$lockedUntil", now]};
+    let failures = doc! {"$add": [
+        {"$cond": [
+            {"$lt": ["$lastFailureAt", DateTime::from_millis(now_millis - FAILURE_WINDOW)]},
+            0_i64,
+            {"$ifNull": ["$failures", 0_i64]},
+        ]},
+        1_i64,
+    ]};
+    vec![
+        doc! {"$set": {
+            "failures": {"$cond": [locked.clone(), "$failures", failures]},
+            "lastFailureAt": {"$cond": [locked.clone(), "$lastFailureAt", now]},
+            "reserved": {"$not": [locked]},
+        }},
+        doc! {"$set": {
+            "lockedUntil": {"$cond": [
+                {"$and": ["$reserved", {"$gte": ["$failures", free_attempts]}]},
+                {"$max": [
+                    "$lockedUntil",
+                    {"$add": [now, {"$min": [
+                        {"$multiply": [1000_i64, {"$pow": [
+                            2_i64,
+                            {"$min": [{"$subtract": ["$failures", free_attempts]}, 20_i64]},
+                        ]}]},
+                        MAX_LOCKOUT,
+                    ]}]},
+                ]},
+                "$lockedUntil",
+            ]},
+        }},
+        doc! {"$unset": "reserved"},
+    ]
+}
+
+#[derive(Clone)]
+pub struct LoginAttemptService {
+    collection: Collection<LoginAttempts>,
+}
+
+impl LoginAttemptService {
+    pub fn new(collection: Collection<LoginAttempts>) -> LoginAttemptService {
+        LoginAttemptService { collection }
+    }
+
+    /// Counts a login attempt of each key as failed until `release` or
+    /// `clear` is called, and refuses it when one of them is locked. The
+    /// check and the count are a single update, so parallel attempts cannot
+    /// all pass before the lock is written.
+    pub async fn reserve(&self, keys: &[(&str, i64)]) -> LoginAttemptServiceResult<()> {
+        let mut locked = false;
+        for (key, free_attempts) in keys {
+            locked |= self.reserve_key(key, *free_attempts).await?;
+        }
+        match locked {
+            true => Err(SBError::ServiceError {
+                service: String::from("login_attempts"),
+                message: String::from("Too many failed login attempts, try again later."),
+            }),
+            false => Ok(()),
+        }
+    }
+
+    /// Whether the key was locked, the attempt is only counted otherwise.
+    async fn reserve_key(&self, key: &str, free_attempts: i64) -> LoginAttemptServiceResult<bool> {
+        let now = DateTime::now();
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .build();
+        let attempts = self
+            .collection
+            .clone_with_type::<Document>()
+            .find_one_and_update(
+                doc! {"key": key},
+                UpdateModifications::Pipeline(reserve_pipeline(now, free_attempts)),
+                options,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("login_attempts"),
+                message: String::from("Failure recording login attempt."),
+            })?;
+        Ok(attempts
+            .and_then(|attempts| attempts.get_datetime("lockedUntil").ok().copied())
+            .is_some_and(|locked_until| locked_until > now))
+    }
+
+    /// Uncounts an attempt that turned out successful, without forgetting
+    /// the other failures of the key.
+    pub async fn release(&self, key: &str, free_attempts: i64) -> LoginAttemptServiceResult<()> {
+        self.collection
+            .update_one(
+                doc! {"key": key, "failures": {"$gt": 0_i64}},
+                vec![doc! {"$set": {
+                    "failures": {"$subtract": ["$failures", 1_i64]},
+                    "lockedUntil": {"$cond": [
+                        {"$lte": ["$failures", free_attempts]},
+                        "$$REMOVE",
+                        "$lockedUntil",
+                    ]},
+                }}],
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("login_attempts"),
+                message: String::from("Failure recording login attempt."),
+            })
+    }
+
+    /// Forgets the failures of a key, unlocking it.
+    pub async fn clear(&self, key: &str) -> LoginAttemptServiceResult<()> {
+        self.collection
+            .delete_one(doc! {"key": key}, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("login_attempts"),
+                message: String::from("Failure clearing login attempts."),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockout_duration() {
+        assert_eq!(lockout_duration(4, ACCOUNT_FREE_ATTEMPTS), None);
+        assert_eq!(lockout_duration(5, ACCOUNT_FREE_ATTEMPTS), Some(1000));
+        assert_eq!(lockout_duration(7, ACCOUNT_FREE_ATTEMPTS), Some(4000));
+        assert_eq!(
+            lockout_duration(500, ACCOUNT_FREE_ATTEMPTS),
+            Some(MAX_LOCKOUT)
+        );
+        assert_eq!(account_key("User@Example.com"), "email:user@example.com");
+    }
+
+    #[tokio::test]
+    #[ignore = "needs MongoDB on localhost:27017"]
+    async fn test_parallel_attempts_are_locked_out() {
+        let client = mongodb::Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let collection = client
+            .database("snellbaas_test")
+            .collection::<LoginAttempts>(&format!(
+                "login_attempts_{}",
+                mongodb::bson::oid::ObjectId::new()
+            ));
+        let service = LoginAttemptService::new(collection.clone());
+        let key = account_key("user@example.com");
+        let keys = [(key.as_str(), ACCOUNT_FREE_ATTEMPTS)];
+
+        let attempts = futures::future::join_all((0..20).map(|_| service.reserve(&keys))).await;
+        let allowed = attempts.iter().filter(|attempt| attempt.is_ok()).count();
+        assert_eq!(allowed as i64, ACCOUNT_FREE_ATTEMPTS);
+        let attempts = collection
+            .find_one(doc! {"key": &key}, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(attempts.failures, ACCOUNT_FREE_ATTEMPTS);
+        assert!(attempts.locked_until.unwrap() > DateTime::now());
+
+        service.clear(&key).await.unwrap();
+        service.reserve(&keys).await.unwrap();
+        service.release(&key, ACCOUNT_FREE_ATTEMPTS).await.unwrap();
+        let attempts = collection
+            .find_one(doc! {"key": &key}, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(attempts.failures, 0);
+        collection.drop(None).await.unwrap();
+    }
+}
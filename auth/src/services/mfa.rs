@@ -0,0 +1,279 @@
+use crate::models::mfa::MfaEnrolment;
+use data_encoding::BASE32_NOPAD;
+use error::SBError;
+use hmac::{Hmac, Mac, NewMac};
+use mongodb::bson::{doc, DateTime};
+use mongodb::Collection;
+use rand::RngCore;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type MfaServiceResult<T> = std::result::Result<T, SBError>;
+
+const TOTP_PERIOD: u64 = 30;
+const TOTP_DIGITS: u32 = 6;
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// TOTP code of a time step, as described in RFC 6238 with HMAC-SHA1.
+pub fn totp(secret: &[u8], step: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&step.to_be_bytes());
+    let result = mac.finalize().into_bytes();
+    let offset = (result[result.len() - 1] & 0xf) as usize;
+    let code = u32::from_be_bytes([
+        result[offset] & 0x7f,
+        result[offset + 1],
+        result[offset + 2],
+        result[offset + 3],
+    ]);
+    code % 10_u32.pow(TOTP_DIGITS)
+}
+
+/// Time step matching the code, accepting one step of clock drift.
+pub fn verify_totp(secret: &[u8], code: &str, time: u64) -> Option<u64> {
+    let code = code.trim().parse::<u32>().ok()?;
+    let step = time / TOTP_PERIOD;
+    (step.saturating_sub(1)..=step + 1).find(|candidate| totp(secret, *candidate) == code)
+}
+
+pub fn otpauth_uri(issuer: &str, account: &str, secret: &str) -> String {
+    // Authenticator apps show a `+` in the label as is, spaces are encoded as
+    // `%20` instead. A literal `+` is already encoded as `%2B`.
+    let encode = |value: &str| {
+        url::form_urlencoded::byte_serialize(value.as_bytes())
+            .collect::<String>()
+            .replace('+', "%20")
+    };
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode(issuer),
+        encode(account),
+        secret,
+        encode(issuer),
+        TOTP_DIGITS,
+        TOTP_PERIOD
+    )
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 5];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let code = hex::encode(bytes);
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[derive(Clone)]
+pub struct MfaService {
+    collection: Collection<MfaEnrolment>,
+}
+
+impl MfaService {
+    pub fn new(collection: Collection<MfaEnrolment>) -> MfaService {
+        MfaService { collection }
+    }
+
+    async fn find(&self, user_id: &str) -> MfaServiceResult<Option<MfaEnrolment>> {
+        self.collection
+            .find_one(doc! {"userId": user_id}, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Failure finding MFA enrolment."),
+            })
+    }
+
+    pub async fn is_enabled(&self, user_id: &str) -> MfaServiceResult<bool> {
+        Ok(self
+            .find(user_id)
+            .await?
+            .map(|enrolment| enrolment.confirmed)
+            .unwrap_or(false))
+    }
+
+    /// Starts an enrolment with a new secret, replacing an unconfirmed one.
+    /// Returns the base32 secret.
+    pub async fn enroll(&self, user_id: &str) -> MfaServiceResult<String> {
+        if self.is_enabled(user_id).await? {
+            return Err(SBError::ServiceError {
+                service: String::from("mfa"),
+                message: String::from("MFA already enabled."),
+            });
+        }
+        let mut bytes = [0u8; 20];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let secret = BASE32_NOPAD.encode(&bytes);
+        self.collection
+            .delete_many(doc! {"userId": user_id}, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Failure replacing MFA enrolment."),
+            })?;
+        let enrolment = MfaEnrolment {
+            id: None,
+            user_id: String::from(user_id),
+            secret: secret.clone(),
+            confirmed: false,
+            recovery_code_hashes: vec![],
+            last_used_step: 0,
+            created_at: DateTime::now(),
+        };
+        self.collection
+            .insert_one(enrolment, None)
+            .await
+            .map(|_| secret)
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Could not create MFA enrolment."),
+            })
+    }
+
+    /// Checks a TOTP code, each time step is only accepted once.
+    async fn use_totp(&self, enrolment: &MfaEnrolment, code: &str) -> MfaServiceResult<bool> {
+        let secret = BASE32_NOPAD
+            .decode(enrolment.secret.as_bytes())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Invalid MFA secret."),
+            })?;
+        let step = match verify_totp(&secret, code, now()) {
+            Some(step) => step as i64,
+            None => return Ok(false),
+        };
+        self.collection
+            .update_one(
+                doc! {"_id": enrolment.id, "lastUsedStep": {"$lt": step}},
+                doc! {"$set": {"lastUsedStep": step}},
+                None,
+            )
+            .await
+            .map(|result| result.modified_count == 1)
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Failure verifying MFA code."),
+            })
+    }
+
+    /// Confirms an enrolment with a first code and returns the recovery
+    /// codes, which are only stored hashed.
+    pub async fn confirm(&self, user_id: &str, code: &str) -> MfaServiceResult<Vec<String>> {
+        let enrolment = match self.find(user_id).await? {
+            Some(enrolment) if !enrolment.confirmed => enrolment,
+            _ => {
+                return Err(SBError::ServiceError {
+                    service: String::from("mfa"),
+                    message: String::from("No pending MFA enrolment."),
+                })
+            }
+        };
+        if !self.use_totp(&enrolment, code).await? {
+            return Err(SBError::ServiceError {
+                service: String::from("mfa"),
+                message: String::from("Invalid MFA code."),
+            });
+        }
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+        let hashes: Vec<String> = recovery_codes
+            .iter()
+            .map(|code| hash_recovery_code(code))
+            .collect();
+        self.collection
+            .update_one(
+                doc! {"_id": enrolment.id},
+                doc! {"$set": {"confirmed": true, "recoveryCodeHashes": hashes}},
+                None,
+            )
+            .await
+            .map(|_| recovery_codes)
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Failure confirming MFA enrolment."),
+            })
+    }
+
+    /// Checks a TOTP code or consumes a recovery code of an enabled
+    /// enrolment.
+    pub async fn verify(&self, user_id: &str, code: &str) -> MfaServiceResult<bool> {
+        let enrolment = match self.find(user_id).await? {
+            Some(enrolment) if enrolment.confirmed => enrolment,
+            _ => return Ok(false),
+        };
+        if self.use_totp(&enrolment, code).await? {
+            return Ok(true);
+        }
+        let hash = hash_recovery_code(code);
+        self.collection
+            .update_one(
+                doc! {"_id": enrolment.id, "recoveryCodeHashes": &hash},
+                doc! {"$pull": {"recoveryCodeHashes": &hash}},
+                None,
+            )
+            .await
+            .map(|result| result.modified_count == 1)
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Failure verifying MFA code."),
+            })
+    }
+
+    pub async fn disable(&self, user_id: &str) -> MfaServiceResult<()> {
+        self.collection
+            .delete_many(doc! {"userId": user_id}, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mfa"),
+                message: String::from("Failure disabling MFA."),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_totp_rfc6238_vectors() {
+        let secret = b"12345678901234567890";
+        assert_eq!(totp(secret, 59 / TOTP_PERIOD), 287082);
+        assert_eq!(totp(secret, 1111111109 / TOTP_PERIOD), 81804);
+        assert_eq!(verify_totp(secret, "287082", 59), Some(1));
+        assert_eq!(verify_totp(secret, "287082", 89), Some(1));
+        assert_eq!(verify_totp(secret, "287082", 150), None);
+    }
+
+    #[test]
+    fn test_recovery_codes_and_uri() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(
+            hash_recovery_code(&code),
+            hash_recovery_code(&code.replace('-', "").to_uppercase())
+        );
+        assert_eq!(
+            otpauth_uri("Snell Baas", "user@example.com", "ABC"),
+            "otpauth://totp/Snell%20Baas:user%40example.com?secret=ABC&issuer=Snell%20Baas&algorithm=SHA1&digits=6&period=30"
+        );
+        assert!(otpauth_uri("A+B", "a+b@example.com", "ABC")
+            .starts_with("otpauth://totp/A%2BB:a%2Bb%40example.com?"));
+    }
+}
@@ -1,21 +1,781 @@
+use crate::http;
+use crate::mailer::{self, Mailer};
+use crate::models::mfa::{MfaChallengeClaims, MfaEnrolmentResponse};
+use crate::models::oidc::{Identity, OidcProvider};
+use crate::models::sessions::{ClientInfo, SessionInfo};
+use crate::models::tokens::ActionTokenKind;
+use crate::models::users::{AccountStatus, Claims, User};
+use chrono::prelude::*;
+use error::{SBError, SBResult};
+use mongodb::bson::{self, doc, document::Document};
 use mongodb::Database;
 
+pub mod keys;
+pub mod login_attempts;
+pub mod mfa;
+pub mod oidc;
+pub mod password_hashing;
+pub mod password_policy;
+pub mod sessions;
+pub mod status_cache;
+pub mod tokens;
 pub mod users;
 
+/// Lifetime of an access token, in seconds. Sessions are kept alive with
+/// refresh tokens.
+const ACCESS_TOKEN_DURATION: usize = 900;
+/// Lifetime of the token proving the password while the second factor is
+/// asked, in seconds.
+const MFA_CHALLENGE_DURATION: usize = 300;
+/// Issuer shown by authenticator apps.
+const MFA_ISSUER: &str = "SnellBaas";
+
+/// Why a login is refused, `None` when the account is active at `now`.
+pub fn status_refusal(status: &AccountStatus, now: bson::DateTime) -> Option<String> {
+    if status.is_active_at(now) {
+        return None;
+    }
+    match status {
+        AccountStatus::Banned { until } => Some(format!(
+            "Account banned until {}.",
+            Utc.timestamp_millis(until.timestamp_millis()).to_rfc3339()
+        )),
+        _ => Some(String::from("Account disabled.")),
+    }
+}
+
+/// Keys of `app_metadata` to embed as custom claims, `None` when the user
+/// has none of them.
+pub fn select_claims(app_metadata: &Document, keys: &[String]) -> Option<Document> {
+    let selected: Document = keys
+        .iter()
+        .filter_map(|key| {
+            app_metadata
+                .get(key)
+                .map(|value| (key.clone(), value.clone()))
+        })
+        .collect();
+    match selected.is_empty() {
+        true => None,
+        false => Some(selected),
+    }
+}
+
 #[derive(Clone)]
 pub struct AuthenticationService {
     pub db: Database,
-    pub secret: String,
+    pub keys: keys::KeyStore,
     pub users: users::UserService,
+    pub sessions: sessions::SessionService,
+    pub tokens: tokens::ActionTokenService,
+    pub login_attempts: login_attempts::LoginAttemptService,
+    pub mfa: mfa::MfaService,
+    pub oidc: oidc::OidcService,
+    /// App metadata keys embedded in the tokens.
+    pub custom_claims: Vec<String>,
+    /// Set in strict mode, where the extractors check the account status of
+    /// every request.
+    pub status_cache: Option<status_cache::StatusCache>,
 }
 
 impl AuthenticationService {
-    pub fn init(db: Database, collection_name: String, secret: String) -> AuthenticationService {
+    pub fn init(
+        db: Database,
+        collection_name: String,
+        keys: keys::KeyStore,
+        password_policy: password_policy::PasswordPolicy,
+        password_hashing: password_hashing::PasswordHashing,
+    ) -> AuthenticationService {
         let collection = db.collection(collection_name.as_ref());
+        let session_collection = db.collection(&format!("{}_sessions", collection_name));
+        let token_collection = db.collection(&format!("{}_tokens", collection_name));
+        let attempt_collection = db.collection(&format!("{}_login_attempts", collection_name));
+        let mfa_collection = db.collection(&format!("{}_mfa", collection_name));
+        let oidc_collection = db.collection(&format!("{}_oidc_flows", collection_name));
         AuthenticationService {
-            db: db,
-            secret: secret,
-            users: users::UserService::new(collection),
+            db,
+            keys,
+            users: users::UserService::new(collection, password_policy, password_hashing),
+            sessions: sessions::SessionService::new(session_collection),
+            tokens: tokens::ActionTokenService::new(token_collection),
+            login_attempts: login_attempts::LoginAttemptService::new(attempt_collection),
+            mfa: mfa::MfaService::new(mfa_collection),
+            oidc: oidc::OidcService::new(oidc_collection),
+            custom_claims: vec![],
+            status_cache: None,
         }
     }
+
+    /// Signs in with identity providers through `client`, sharing its cached
+    /// provider documents.
+    pub fn with_http_client(mut self, client: http::Client) -> AuthenticationService {
+        self.oidc = self.oidc.with_client(client);
+        self
+    }
+
+    /// Embeds the app metadata keys `keys` of the user as custom claims in
+    /// the tokens issued from now on, refreshed tokens included.
+    pub fn with_custom_claims(mut self, keys: Vec<String>) -> AuthenticationService {
+        self.custom_claims = keys;
+        self
+    }
+
+    /// Enables strict mode, account statuses are cached in `cache`.
+    pub fn with_status_cache(mut self, cache: status_cache::StatusCache) -> AuthenticationService {
+        self.status_cache = Some(cache);
+        self
+    }
+
+    async fn issue_tokens(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        refresh_token: &str,
+        audience: Option<String>,
+    ) -> SBResult<Document> {
+        let app_metadata = match self.custom_claims.is_empty() {
+            true => None,
+            false => {
+                let user = self.users.get(user_id).await?;
+                select_claims(&user.app_metadata, &self.custom_claims)
+            }
+        };
+        let my_claims = Claims {
+            sub: String::from(user_id),
+            exp: Utc::now().timestamp() as usize + ACCESS_TOKEN_DURATION,
+            aud: audience,
+            sid: Some(String::from(session_id)),
+            app_metadata,
+        };
+        let token = self.keys.encode(&my_claims)?;
+        Ok(doc! {
+            "token": token,
+            "refreshToken": refresh_token,
+            "expiresIn": ACCESS_TOKEN_DURATION as i64,
+            "success": true,
+        })
+    }
+
+    fn issue_mfa_challenge(&self, user_id: &str, audience: Option<String>) -> SBResult<Document> {
+        let claims = MfaChallengeClaims {
+            sub: String::from(user_id),
+            exp: Utc::now().timestamp() as usize + MFA_CHALLENGE_DURATION,
+            aud: audience,
+            mfa_challenge: true,
+        };
+        let token = self.keys.encode(&claims)?;
+        Ok(doc! {
+            "mfaRequired": true,
+            "mfaToken": token,
+            "success": false,
+        })
+    }
+
+    fn ensure_active(user: &User) -> SBResult<()> {
+        match status_refusal(&user.status, bson::DateTime::now()) {
+            Some(message) => Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Counts a login attempt as failed for the account and the client,
+    /// refused while either of them is locked out.
+    async fn reserve_login_attempt(
+        &self,
+        account_key: &str,
+        client_key: Option<&str>,
+    ) -> SBResult<()> {
+        let mut keys = vec![(account_key, login_attempts::ACCOUNT_FREE_ATTEMPTS)];
+        keys.extend(client_key.map(|key| (key, login_attempts::CLIENT_FREE_ATTEMPTS)));
+        self.login_attempts.reserve(&keys).await
+    }
+
+    async fn release_client_attempt(&self, client_key: Option<&str>) -> SBResult<()> {
+        match client_key {
+            Some(client_key) => {
+                self.login_attempts
+                    .release(client_key, login_attempts::CLIENT_FREE_ATTEMPTS)
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the credentials of a user and opens a new session for them.
+    /// Failed attempts lock the account and the IP of `client` out for a
+    /// growing duration. Users with two-factor authentication get an MFA
+    /// challenge token instead, to exchange with `verify_mfa`.
+    pub async fn authenticate(
+        &self,
+        email: &str,
+        password: &str,
+        audience: Option<String>,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        let account_key = login_attempts::account_key(email);
+        let client_key = client.ip.as_deref().map(login_attempts::client_key);
+        self.reserve_login_attempt(&account_key, client_key.as_deref())
+            .await?;
+
+        let user = self.users.verify_credentials(email, password).await?;
+        self.release_client_attempt(client_key.as_deref()).await?;
+        Self::ensure_active(&user)?;
+        if user.password_reset_required {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Password reset required."),
+            });
+        }
+        let user_id = user.id.unwrap().to_hex();
+        // The failures are only cleared once every factor is verified, so
+        // the password alone does not reset the lockout between codes.
+        if self.mfa.is_enabled(&user_id).await? {
+            return self.issue_mfa_challenge(&user_id, audience);
+        }
+        self.login_attempts.clear(&account_key).await?;
+        let (session_id, refresh_token) = self.sessions.create(&user_id, client).await?;
+        self.issue_tokens(&user_id, &session_id, &refresh_token, audience)
+            .await
+    }
+
+    /// Completes a login with the MFA challenge token of `authenticate` and
+    /// a TOTP or recovery code. Wrong codes count as failed logins of the
+    /// account, which only a verified code clears.
+    pub async fn verify_mfa(
+        &self,
+        mfa_token: &str,
+        code: &str,
+        audience: Option<String>,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        let claims = self
+            .keys
+            .decode::<MfaChallengeClaims>(mfa_token, audience.as_deref())?;
+        if !claims.mfa_challenge {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Invalid token."),
+            });
+        }
+        let user = self.users.get(&claims.sub).await?;
+        Self::ensure_active(&user)?;
+        let account_key = login_attempts::account_key(&user.email);
+        let client_key = client.ip.as_deref().map(login_attempts::client_key);
+        self.reserve_login_attempt(&account_key, client_key.as_deref())
+            .await?;
+
+        if !self.mfa.verify(&claims.sub, code).await? {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Invalid MFA code."),
+            });
+        }
+        self.release_client_attempt(client_key.as_deref()).await?;
+        self.login_attempts.clear(&account_key).await?;
+        let (session_id, refresh_token) = self.sessions.create(&claims.sub, client).await?;
+        self.issue_tokens(&claims.sub, &session_id, &refresh_token, audience)
+            .await
+    }
+
+    /// Starts a TOTP enrolment, the returned secret has to be confirmed
+    /// with a first code by `confirm_mfa`.
+    pub async fn enroll_mfa(&self, user_id: &str) -> SBResult<MfaEnrolmentResponse> {
+        let user = self.users.get(user_id).await?;
+        let secret = self.mfa.enroll(user_id).await?;
+        Ok(MfaEnrolmentResponse {
+            otpauth_uri: mfa::otpauth_uri(MFA_ISSUER, &user.email, &secret),
+            secret,
+        })
+    }
+
+    /// Enables two-factor authentication and returns the recovery codes,
+    /// they are only shown once.
+    pub async fn confirm_mfa(&self, user_id: &str, code: &str) -> SBResult<Document> {
+        let recovery_codes = self.mfa.confirm(user_id, code).await?;
+        Ok(doc! {"recoveryCodes": recovery_codes, "success": true})
+    }
+
+    /// Disables two-factor authentication, a current code is required so a
+    /// stolen access token is not enough. Wrong codes count as failed logins
+    /// of the account, like in `verify_mfa`.
+    pub async fn disable_mfa(&self, user_id: &str, code: &str) -> SBResult<Document> {
+        let user = self.users.get(user_id).await?;
+        let account_key = login_attempts::account_key(&user.email);
+        self.reserve_login_attempt(&account_key, None).await?;
+        if !self.mfa.verify(user_id, code).await? {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Invalid MFA code."),
+            });
+        }
+        self.login_attempts.clear(&account_key).await?;
+        self.mfa.disable(user_id).await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Starts a sign in with an external provider and returns the URL of
+    /// the provider page. With `link_user_id` the identity is added to that
+    /// user instead.
+    pub async fn start_oidc(
+        &self,
+        provider: &OidcProvider,
+        redirect_uri: &str,
+        link_user_id: Option<&str>,
+    ) -> SBResult<Document> {
+        let url = self
+            .oidc
+            .start(provider, redirect_uri, link_user_id)
+            .await?;
+        Ok(doc! {"url": url})
+    }
+
+    /// Completes a sign in with an external provider. The identity is looked
+    /// up first, then matched by email when both the provider and the user
+    /// verified it, otherwise a new user is created.
+    pub async fn finish_oidc(
+        &self,
+        providers: &[OidcProvider],
+        state: &str,
+        code: &str,
+        audience: Option<String>,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        let (flow, provider, profile) = self.oidc.finish(providers, state, code).await?;
+        let identity = Identity {
+            provider: provider.name.clone(),
+            subject: profile.subject.clone(),
+            email: profile.email.clone(),
+        };
+        if let Some(user_id) = flow.link_user_id {
+            let anonymous = self.users.get(&user_id).await?.anonymous;
+            if anonymous {
+                if let Some(email) = &profile.email {
+                    if self.users.email_in_use(email).await? {
+                        return Err(SBError::ServiceError {
+                            service: String::from("authentication"),
+                            message: String::from("Username already in use."),
+                        });
+                    }
+                }
+            }
+            self.users.link_identity(&user_id, identity).await?;
+            if anonymous {
+                self.users.upgrade_with_profile(&user_id, &profile).await?;
+            }
+            return Ok(doc! {"success": true});
+        }
+
+        let user_id =
+            match self
+                .users
+                .get_by_identity(&identity.provider, &identity.subject)
+                .await?
+            {
+                Some(user) => {
+                    Self::ensure_active(&user)?;
+                    user.id.unwrap().to_hex()
+                }
+                None => {
+                    let email = profile.email.clone().ok_or_else(|| SBError::ServiceError {
+                        service: String::from("authentication"),
+                        message: String::from("The provider did not share an email address."),
+                    })?;
+                    match self.users.get_by_email(&email).await {
+                        Ok(user) if user.email_verified && profile.email_verified => {
+                            Self::ensure_active(&user)?;
+                            let user_id = user.id.unwrap().to_hex();
+                            self.users.link_identity(&user_id, identity).await?;
+                            user_id
+                        }
+                        Ok(_) => return Err(SBError::ServiceError {
+                            service: String::from("authentication"),
+                            message: String::from(
+                                "An account already uses this email, sign in to link the provider.",
+                            ),
+                        }),
+                        Err(SBError::ServiceError { .. }) => {
+                            let mut username = profile.username.clone().unwrap_or_else(|| {
+                                email.split('@').next().unwrap_or_default().into()
+                            });
+                            if username.len() < 3 || self.users.username_exists(&username).await? {
+                                username = format!("{}-{}", provider.name, profile.subject);
+                            }
+                            let user = User {
+                                username,
+                                first_name: profile.first_name.clone().unwrap_or_default(),
+                                last_name: profile.last_name.clone().unwrap_or_default(),
+                                email,
+                                email_verified: profile.email_verified,
+                                identities: vec![identity],
+                                ..Default::default()
+                            };
+                            self.users.create_external(user).await?._id
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+            };
+        if self.mfa.is_enabled(&user_id).await? {
+            return self.issue_mfa_challenge(&user_id, audience);
+        }
+        let (session_id, refresh_token) = self.sessions.create(&user_id, client).await?;
+        self.issue_tokens(&user_id, &session_id, &refresh_token, audience)
+            .await
+    }
+
+    /// Opens a session for a new guest user, which can later be upgraded
+    /// with `upgrade_anonymous` or by linking an identity.
+    pub async fn sign_in_anonymously(
+        &self,
+        audience: Option<String>,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        let user_id = self.users.create_anonymous().await?._id;
+        let (session_id, refresh_token) = self.sessions.create(&user_id, client).await?;
+        self.issue_tokens(&user_id, &session_id, &refresh_token, audience)
+            .await
+    }
+
+    /// Gives a guest user an email and a password, its id and sessions are
+    /// kept.
+    pub async fn upgrade_anonymous(
+        &self,
+        user_id: &str,
+        email: &str,
+        password: &str,
+        username: Option<&str>,
+    ) -> SBResult<Document> {
+        self.users
+            .upgrade(user_id, email, password, username)
+            .await
+            .map(|_| doc! {"success": true})
+    }
+
+    /// Mails a single use sign in token when the email belongs to a user,
+    /// the response is the same for unknown emails.
+    pub async fn request_magic_link(
+        &self,
+        email: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        let user = match self.users.get_by_email(email).await {
+            Ok(user) => user,
+            Err(SBError::ServiceError { .. }) => return Ok(doc! {"success": true}),
+            Err(error) => return Err(error),
+        };
+        let user_id = user.id.unwrap().to_hex();
+        let token = self
+            .tokens
+            .create(&user_id, &user.email, ActionTokenKind::MagicLink)
+            .await?;
+        mailer
+            .send(mailer::magic_link_mail(&user.email, &token, link))
+            .await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Signs in with a magic link token, which also proves the user owns
+    /// their email address.
+    pub async fn sign_in_with_magic_link(
+        &self,
+        token: &str,
+        audience: Option<String>,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        let action_token = self
+            .tokens
+            .consume(token, ActionTokenKind::MagicLink)
+            .await?;
+        let user = self.users.get(&action_token.user_id).await?;
+        if user.email != action_token.email {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Invalid or expired token."),
+            });
+        }
+        Self::ensure_active(&user)?;
+        let user_id = action_token.user_id;
+        if !user.email_verified {
+            self.users.set_email_verified(&user_id).await?;
+        }
+        if self.mfa.is_enabled(&user_id).await? {
+            return self.issue_mfa_challenge(&user_id, audience);
+        }
+        let (session_id, refresh_token) = self.sessions.create(&user_id, client).await?;
+        self.issue_tokens(&user_id, &session_id, &refresh_token, audience)
+            .await
+    }
+
+    pub async fn refresh(
+        &self,
+        refresh_token: &str,
+        audience: Option<String>,
+    ) -> SBResult<Document> {
+        let (session, refresh_token) = self.sessions.rotate(refresh_token).await?;
+        Self::ensure_active(&self.users.get(&session.user_id).await?)?;
+        let session_id = session.id.unwrap().to_hex();
+        self.issue_tokens(&session.user_id, &session_id, &refresh_token, audience)
+            .await
+    }
+
+    pub async fn logout(&self, session_id: &str) -> SBResult<Document> {
+        self.sessions
+            .revoke(session_id)
+            .await
+            .map(|_| doc! {"success": true})
+    }
+
+    /// Active sessions of the user, `session_id` is marked as the current
+    /// one.
+    pub async fn list_sessions(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> SBResult<Vec<SessionInfo>> {
+        Ok(self
+            .sessions
+            .list(user_id)
+            .await?
+            .into_iter()
+            .map(|session| SessionInfo::from_session(session, session_id))
+            .collect())
+    }
+
+    /// Signs the user out of one of their sessions.
+    pub async fn revoke_session(&self, user_id: &str, session_id: &str) -> SBResult<Document> {
+        self.sessions
+            .revoke_for_user(user_id, session_id)
+            .await
+            .map(|_| doc! {"success": true})
+    }
+
+    /// Signs the user out of every session but `session_id`.
+    pub async fn revoke_other_sessions(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> SBResult<Document> {
+        self.sessions
+            .revoke_all(user_id, Some(session_id))
+            .await
+            .map(|_| doc! {"success": true})
+    }
+
+    /// Mails a verification token to the user. `link` is the page receiving
+    /// the token as `?token=` query parameter.
+    pub async fn request_email_verification(
+        &self,
+        user_id: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        let user = self.users.get(user_id).await?;
+        if user.email_verified {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Email already verified."),
+            });
+        }
+        let token = self
+            .tokens
+            .create(user_id, &user.email, ActionTokenKind::EmailVerification)
+            .await?;
+        mailer
+            .send(mailer::email_verification_mail(&user.email, &token, link))
+            .await?;
+        Ok(doc! {"success": true})
+    }
+
+    pub async fn verify_email(&self, token: &str) -> SBResult<Document> {
+        let action_token = self
+            .tokens
+            .consume(token, ActionTokenKind::EmailVerification)
+            .await?;
+        let user = self.users.get(&action_token.user_id).await?;
+        if user.email != action_token.email {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Invalid or expired token."),
+            });
+        }
+        self.users.set_email_verified(&action_token.user_id).await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Mails a reset token when the email belongs to a user. The response
+    /// is the same for unknown emails so it cannot be used to find accounts.
+    pub async fn request_password_reset(
+        &self,
+        email: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        let user = match self.users.get_by_email(email).await {
+            Ok(user) => user,
+            Err(SBError::ServiceError { .. }) => return Ok(doc! {"success": true}),
+            Err(error) => return Err(error),
+        };
+        let user_id = user.id.unwrap().to_hex();
+        let token = self
+            .tokens
+            .create(&user_id, &user.email, ActionTokenKind::PasswordReset)
+            .await?;
+        mailer
+            .send(mailer::password_reset_mail(&user.email, &token, link))
+            .await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Sets a new password and signs the user out of all their sessions.
+    pub async fn reset_password(&self, token: &str, password: &str) -> SBResult<Document> {
+        let action_token = self
+            .tokens
+            .consume(token, ActionTokenKind::PasswordReset)
+            .await?;
+        let user = self.users.get(&action_token.user_id).await?;
+        if user.email != action_token.email {
+            return Err(SBError::ServiceError {
+                service: String::from("authentication"),
+                message: String::from("Invalid or expired token."),
+            });
+        }
+        Self::ensure_active(&user)?;
+        self.users
+            .set_password(&action_token.user_id, password)
+            .await?;
+        self.sessions
+            .revoke_all(&action_token.user_id, None)
+            .await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Sets a new password and signs the user out of their other sessions.
+    pub async fn change_password(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> SBResult<Document> {
+        self.users
+            .change_password(user_id, current_password, new_password)
+            .await?;
+        self.sessions.revoke_all(user_id, Some(session_id)).await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Sets the status of a user, disabled and banned users are signed out
+    /// of all their sessions.
+    pub async fn set_status(&self, user_id: &str, status: &AccountStatus) -> SBResult<Document> {
+        self.users.set_status(user_id, status).await?;
+        if let Some(cache) = &self.status_cache {
+            cache.invalidate(user_id);
+        }
+        if *status != AccountStatus::Active {
+            self.sessions.revoke_all(user_id, None).await?;
+        }
+        Ok(doc! {"success": true})
+    }
+
+    /// Whether a user may keep using their tokens, cached in strict mode.
+    /// Deleted users are not active.
+    pub async fn is_user_active(&self, user_id: &str) -> SBResult<bool> {
+        if let Some(active) = self
+            .status_cache
+            .as_ref()
+            .and_then(|cache| cache.get(user_id))
+        {
+            return Ok(active);
+        }
+        let active = match self.users.get(user_id).await {
+            Ok(user) => user.status.is_active_at(bson::DateTime::now()),
+            Err(SBError::ServiceError { .. }) => false,
+            Err(error) => return Err(error),
+        };
+        if let Some(cache) = &self.status_cache {
+            cache.insert(user_id, active);
+        }
+        Ok(active)
+    }
+
+    /// Refuses password logins of a user until they set a new password with
+    /// the mailed reset token, and signs them out of all their sessions.
+    pub async fn force_password_reset(
+        &self,
+        user_id: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        let user = self.users.get(user_id).await?;
+        self.users.require_password_reset(user_id).await?;
+        self.sessions.revoke_all(user_id, None).await?;
+        let token = self
+            .tokens
+            .create(user_id, &user.email, ActionTokenKind::PasswordReset)
+            .await?;
+        mailer
+            .send(mailer::password_reset_mail(&user.email, &token, link))
+            .await?;
+        Ok(doc! {"success": true})
+    }
+
+    /// Deletes a user with their sessions and second factor.
+    pub async fn delete_user(&self, user_id: &str) -> SBResult<Document> {
+        self.users.get(user_id).await?;
+        self.sessions.revoke_all(user_id, None).await?;
+        self.mfa.disable(user_id).await?;
+        if let Some(cache) = &self.status_cache {
+            cache.invalidate(user_id);
+        }
+        self.users.delete(user_id).await
+    }
+
+    /// Lifts the lockout of an account after failed logins.
+    pub async fn unlock(&self, email: &str) -> SBResult<Document> {
+        self.login_attempts
+            .clear(&login_attempts::account_key(email))
+            .await
+            .map(|_| doc! {"success": true})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_refusal() {
+        let now = bson::DateTime::from_millis(1_000_000);
+        assert_eq!(status_refusal(&AccountStatus::Active, now), None);
+        assert_eq!(
+            status_refusal(&AccountStatus::Disabled, now).as_deref(),
+            Some("Account disabled.")
+        );
+        let banned = AccountStatus::Banned {
+            until: bson::DateTime::from_millis(2_000_000),
+        };
+        assert_eq!(
+            status_refusal(&banned, now).as_deref(),
+            Some("Account banned until 1970-01-01T00:33:20+00:00.")
+        );
+        assert_eq!(
+            status_refusal(&banned, bson::DateTime::from_millis(2_000_000)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_select_claims() {
+        let app_metadata = doc! {"roles": ["editor"], "plan": "pro", "internal": 1};
+        let keys = vec![String::from("roles"), String::from("tenant")];
+        assert_eq!(
+            select_claims(&app_metadata, &keys),
+            Some(doc! {"roles": ["editor"]})
+        );
+        assert_eq!(select_claims(&app_metadata, &[]), None);
+    }
 }
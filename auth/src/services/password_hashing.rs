@@ -0,0 +1,154 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Argon2, Params, Version};
+use error::{SBError, SBResult};
+use rand::RngCore;
+use std::convert::TryFrom;
+
+pub const DEFAULT_BCRYPT_COST: u32 = 12;
+/// Recommended Argon2id parameters, 19 MiB of memory and 2 iterations.
+pub const DEFAULT_ARGON2_MEMORY_COST: u32 = 19456;
+pub const DEFAULT_ARGON2_TIME_COST: u32 = 2;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+
+/// Algorithm and parameters of new password hashes. Hashes made with other
+/// parameters are still verified and are replaced on the next login.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordHashing {
+    Bcrypt {
+        cost: u32,
+    },
+    Argon2id {
+        memory_cost: u32,
+        time_cost: u32,
+        parallelism: u32,
+    },
+}
+
+impl Default for PasswordHashing {
+    fn default() -> Self {
+        PasswordHashing::Bcrypt {
+            cost: DEFAULT_BCRYPT_COST,
+        }
+    }
+}
+
+fn hashing_error(message: &str) -> SBError {
+    SBError::InternalServiceError {
+        service: String::from("password_hashing"),
+        message: String::from(message),
+    }
+}
+
+impl PasswordHashing {
+    pub fn argon2id() -> PasswordHashing {
+        PasswordHashing::Argon2id {
+            memory_cost: DEFAULT_ARGON2_MEMORY_COST,
+            time_cost: DEFAULT_ARGON2_TIME_COST,
+            parallelism: DEFAULT_ARGON2_PARALLELISM,
+        }
+    }
+
+    fn argon2(memory_cost: u32, time_cost: u32, parallelism: u32) -> SBResult<Argon2<'static>> {
+        let params = Params::new(memory_cost, time_cost, parallelism, None)
+            .map_err(|_| hashing_error("Invalid Argon2 parameters."))?;
+        Ok(Argon2::new(
+            argon2::Algorithm::Argon2id,
+            Version::V0x13,
+            params,
+        ))
+    }
+
+    pub fn hash(&self, password: &str) -> SBResult<String> {
+        match *self {
+            PasswordHashing::Bcrypt { cost } => {
+                bcrypt::hash(password, cost).map_err(|_| hashing_error("Failure hashing password"))
+            }
+            PasswordHashing::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => {
+                let mut salt = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut salt);
+                let salt = SaltString::b64_encode(&salt)
+                    .map_err(|_| hashing_error("Failure hashing password"))?;
+                PasswordHashing::argon2(memory_cost, time_cost, parallelism)?
+                    .hash_password(password.as_bytes(), &salt)
+                    .map(|hash| hash.to_string())
+                    .map_err(|_| hashing_error("Failure hashing password"))
+            }
+        }
+    }
+
+    /// Verifies a password against a bcrypt or Argon2 hash, whatever the
+    /// configured algorithm.
+    pub fn verify(password: &str, hash: &str) -> SBResult<bool> {
+        if hash.starts_with("$argon2") {
+            let parsed =
+                PasswordHash::new(hash).map_err(|_| hashing_error("Invalid password hash."))?;
+            return Ok(Argon2::default()
+                .verify_password(password.as_bytes(), &parsed)
+                .is_ok());
+        }
+        bcrypt::verify(password, hash).map_err(|_| hashing_error("Failure verifying password."))
+    }
+
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        match *self {
+            PasswordHashing::Bcrypt { cost } => {
+                !hash.starts_with("$2")
+                    || hash.get(4..6).and_then(|c| c.parse::<u32>().ok()) != Some(cost)
+            }
+            PasswordHashing::Argon2id {
+                memory_cost,
+                time_cost,
+                parallelism,
+            } => match PasswordHash::new(hash) {
+                Ok(parsed) => {
+                    parsed.algorithm != argon2::Algorithm::Argon2id.ident()
+                        || parsed.version != Some(u32::from(Version::V0x13))
+                        || Params::try_from(&parsed)
+                            .map(|params| {
+                                (params.m_cost(), params.t_cost(), params.p_cost())
+                                    != (memory_cost, time_cost, parallelism)
+                            })
+                            .unwrap_or(true)
+                }
+                Err(_) => true,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bcrypt_rehash() {
+        let hash = PasswordHashing::Bcrypt { cost: 4 }
+            .hash("password")
+            .unwrap();
+        assert!(PasswordHashing::verify("password", &hash).unwrap());
+        assert!(!PasswordHashing::verify("other", &hash).unwrap());
+        assert!(!PasswordHashing::Bcrypt { cost: 4 }.needs_rehash(&hash));
+        assert!(PasswordHashing::Bcrypt { cost: 5 }.needs_rehash(&hash));
+        assert!(PasswordHashing::argon2id().needs_rehash(&hash));
+    }
+
+    #[test]
+    fn test_argon2id_rehash() {
+        let hashing = PasswordHashing::Argon2id {
+            memory_cost: 1024,
+            time_cost: 1,
+            parallelism: 1,
+        };
+        let hash = hashing.hash("password").unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(PasswordHashing::verify("password", &hash).unwrap());
+        assert!(!PasswordHashing::verify("other", &hash).unwrap());
+        assert!(!hashing.needs_rehash(&hash));
+        assert!(PasswordHashing::argon2id().needs_rehash(&hash));
+        assert!(PasswordHashing::Bcrypt { cost: 4 }.needs_rehash(&hash));
+    }
+}
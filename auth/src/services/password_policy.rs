@@ -0,0 +1,165 @@
+use error::{SBError, SBResult};
+use std::collections::HashSet;
+use std::fs;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PasswordRule {
+    MinLength,
+    MaxLength,
+    Lowercase,
+    Uppercase,
+    Digit,
+    Symbol,
+    Breached,
+}
+
+impl PasswordRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PasswordRule::MinLength => "minLength",
+            PasswordRule::MaxLength => "maxLength",
+            PasswordRule::Lowercase => "lowercase",
+            PasswordRule::Uppercase => "uppercase",
+            PasswordRule::Digit => "digit",
+            PasswordRule::Symbol => "symbol",
+            PasswordRule::Breached => "breached",
+        }
+    }
+}
+
+/// Rules a new password has to follow. Breached passwords are compared
+/// case-insensitively.
+#[derive(Debug, Clone)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub require_lowercase: bool,
+    pub require_uppercase: bool,
+    pub require_digit: bool,
+    pub require_symbol: bool,
+    breached_passwords: Arc<HashSet<String>>,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            max_length: 128,
+            require_lowercase: false,
+            require_uppercase: false,
+            require_digit: false,
+            require_symbol: false,
+            breached_passwords: Arc::new(HashSet::new()),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn with_breached_passwords(mut self, passwords: HashSet<String>) -> PasswordPolicy {
+        self.breached_passwords = Arc::new(
+            passwords
+                .into_iter()
+                .map(|password| password.to_lowercase())
+                .collect(),
+        );
+        self
+    }
+
+    /// Loads a list of breached passwords, one per line.
+    pub fn load_breached_passwords(path: &str) -> SBResult<HashSet<String>> {
+        let content = fs::read_to_string(path).map_err(|_| SBError::InternalServiceError {
+            service: String::from("password_policy"),
+            message: String::from("Failure reading breached passwords."),
+        })?;
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    pub fn broken_rules(&self, password: &str) -> Vec<PasswordRule> {
+        let length = password.chars().count();
+        let checks = [
+            (length >= self.min_length, PasswordRule::MinLength),
+            (length <= self.max_length, PasswordRule::MaxLength),
+            (
+                !self.require_lowercase || password.chars().any(char::is_lowercase),
+                PasswordRule::Lowercase,
+            ),
+            (
+                !self.require_uppercase || password.chars().any(char::is_uppercase),
+                PasswordRule::Uppercase,
+            ),
+            (
+                !self.require_digit || password.chars().any(|c| c.is_ascii_digit()),
+                PasswordRule::Digit,
+            ),
+            (
+                !self.require_symbol || password.chars().any(|c| !c.is_alphanumeric()),
+                PasswordRule::Symbol,
+            ),
+            (
+                !self.breached_passwords.contains(&password.to_lowercase()),
+                PasswordRule::Breached,
+            ),
+        ];
+        checks
+            .iter()
+            .filter(|(passed, _)| !passed)
+            .map(|(_, rule)| *rule)
+            .collect()
+    }
+
+    pub fn validate(&self, password: &str) -> SBResult<()> {
+        let broken_rules = self.broken_rules(password);
+        if broken_rules.is_empty() {
+            return Ok(());
+        }
+        Err(SBError::ValidationError {
+            service: String::from("password_policy"),
+            errors: broken_rules
+                .iter()
+                .map(|rule| String::from(rule.name()))
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broken_rules() {
+        let policy = PasswordPolicy {
+            require_uppercase: true,
+            require_digit: true,
+            require_symbol: true,
+            ..Default::default()
+        }
+        .with_breached_passwords(vec![String::from("Password1!")].into_iter().collect());
+        assert_eq!(
+            policy.broken_rules("short"),
+            vec![
+                PasswordRule::MinLength,
+                PasswordRule::Uppercase,
+                PasswordRule::Digit,
+                PasswordRule::Symbol
+            ]
+        );
+        assert_eq!(
+            policy.broken_rules("PASSWORD1!"),
+            vec![PasswordRule::Breached]
+        );
+        assert!(policy.validate("Correct-Horse-7").is_ok());
+        match policy.validate("lowercase only") {
+            Err(SBError::ValidationError { errors, .. }) => {
+                assert_eq!(errors, vec!["uppercase", "digit"])
+            }
+            _ => panic!("Expected a validation error"),
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use crate::models::sessions::{ClientInfo, Session};
+use error::SBError;
+use futures::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, DateTime};
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument};
+use mongodb::Collection;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+type SessionServiceResult<T> = std::result::Result<T, SBError>;
+
+/// Lifetime of a session since its last refresh, in milliseconds.
+const SESSION_DURATION: i64 = 30 * 24 * 3600 * 1000;
+/// Shortest delay between two updates of the last seen date, in
+/// milliseconds.
+const LAST_SEEN_RESOLUTION: i64 = 60 * 1000;
+/// Exchanged refresh tokens remembered to detect their reuse, older ones
+/// are only refused as unknown.
+const MAX_USED_TOKEN_HASHES: i32 = 100;
+
+#[derive(Clone)]
+pub struct SessionService {
+    collection: Collection<Session>,
+}
+
+impl SessionService {
+    pub fn new(collection: Collection<Session>) -> SessionService {
+        SessionService { collection }
+    }
+
+    fn generate_refresh_token() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+
+    fn hash_refresh_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    fn expires_at() -> DateTime {
+        DateTime::from_millis(DateTime::now().timestamp_millis() + SESSION_DURATION)
+    }
+
+    fn parse_id(session_id: &str) -> SessionServiceResult<ObjectId> {
+        ObjectId::from_str(session_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("sessions"),
+            message: String::from("Failure making oid object."),
+        })
+    }
+
+    /// Opens a session and returns its id with the first refresh token.
+    pub async fn create(
+        &self,
+        user_id: &str,
+        client: &ClientInfo,
+    ) -> SessionServiceResult<(String, String)> {
+        let refresh_token = SessionService::generate_refresh_token();
+        let now = DateTime::now();
+        let session = Session {
+            id: None,
+            user_id: String::from(user_id),
+            refresh_token_hash: SessionService::hash_refresh_token(&refresh_token),
+            used_token_hashes: vec![],
+            revoked: false,
+            created_at: now,
+            expires_at: SessionService::expires_at(),
+            last_seen_at: Some(now),
+            user_agent: client.user_agent.clone(),
+            ip: client.ip.clone(),
+        };
+        self.collection
+            .insert_one(session, None)
+            .await
+            .map(|r| {
+                (
+                    r.inserted_id.as_object_id().unwrap().to_hex(),
+                    refresh_token,
+                )
+            })
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Could not create session."),
+            })
+    }
+
+    /// Exchanges a refresh token for a new one. Reusing one of the last
+    /// `MAX_USED_TOKEN_HASHES` exchanged tokens revokes the session.
+    pub async fn rotate(&self, refresh_token: &str) -> SessionServiceResult<(Session, String)> {
+        let hash = SessionService::hash_refresh_token(refresh_token);
+        let new_refresh_token = SessionService::generate_refresh_token();
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+        let rotated = self
+            .collection
+            .find_one_and_update(
+                doc! {
+                    "refreshTokenHash": &hash,
+                    "revoked": false,
+                    "expiresAt": {"$gt": DateTime::now()},
+                },
+                doc! {
+                    "$set": {
+                        "refreshTokenHash": SessionService::hash_refresh_token(&new_refresh_token),
+                        "expiresAt": SessionService::expires_at(),
+                        "lastSeenAt": DateTime::now(),
+                    },
+                    "$push": {"usedTokenHashes": {
+                        "$each": [&hash],
+                        "$slice": -MAX_USED_TOKEN_HASHES,
+                    }},
+                },
+                options,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure rotating refresh token."),
+            })?;
+
+        if let Some(session) = rotated {
+            return Ok((session, new_refresh_token));
+        }
+
+        let reused = self
+            .collection
+            .find_one_and_update(
+                doc! {"usedTokenHashes": &hash},
+                doc! {"$set": {"revoked": true}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure rotating refresh token."),
+            })?;
+
+        match reused {
+            Some(_) => Err(SBError::ServiceError {
+                service: String::from("sessions"),
+                message: String::from("Refresh token reuse detected, session revoked."),
+            }),
+            None => Err(SBError::ServiceError {
+                service: String::from("sessions"),
+                message: String::from("Invalid refresh token."),
+            }),
+        }
+    }
+
+    pub async fn revoke(&self, session_id: &str) -> SessionServiceResult<()> {
+        let session_oid = SessionService::parse_id(session_id)?;
+        self.collection
+            .update_one(
+                doc! {"_id": session_oid},
+                doc! {"$set": {"revoked": true}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure revoking session."),
+            })
+    }
+
+    /// Revokes a session of the user, sessions of other users are not found.
+    pub async fn revoke_for_user(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> SessionServiceResult<()> {
+        let session_oid = SessionService::parse_id(session_id)?;
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": session_oid, "userId": user_id},
+                doc! {"$set": {"revoked": true}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure revoking session."),
+            })?;
+        match result.matched_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("sessions"),
+                message: String::from("No session found."),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Signs the user out of every session but `except`.
+    pub async fn revoke_all(
+        &self,
+        user_id: &str,
+        except: Option<&str>,
+    ) -> SessionServiceResult<()> {
+        let mut filter = doc! {"userId": user_id};
+        if let Some(session_id) = except {
+            filter.insert("_id", doc! {"$ne": SessionService::parse_id(session_id)?});
+        }
+        self.collection
+            .update_many(filter, doc! {"$set": {"revoked": true}}, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure revoking sessions."),
+            })
+    }
+
+    pub async fn is_active(&self, session_id: &str) -> SessionServiceResult<bool> {
+        let session_oid = SessionService::parse_id(session_id)?;
+        self.collection
+            .find_one(
+                doc! {
+                    "_id": session_oid,
+                    "revoked": false,
+                    "expiresAt": {"$gt": DateTime::now()},
+                },
+                None,
+            )
+            .await
+            .map(|session| session.is_some())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure finding session."),
+            })
+    }
+
+    /// Active sessions of the user, newest first.
+    pub async fn list(&self, user_id: &str) -> SessionServiceResult<Vec<Session>> {
+        let options = FindOptions::builder().sort(doc! {"createdAt": -1}).build();
+        let cursor = self
+            .collection
+            .find(
+                doc! {
+                    "userId": user_id,
+                    "revoked": false,
+                    "expiresAt": {"$gt": DateTime::now()},
+                },
+                options,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure listing sessions."),
+            })?;
+        cursor
+            .try_collect()
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure listing sessions."),
+            })
+    }
+
+    /// Records that the session was used, skipped when it was recently
+    /// recorded.
+    pub async fn touch(&self, session_id: &str) -> SessionServiceResult<()> {
+        let session_oid = SessionService::parse_id(session_id)?;
+        let now = DateTime::now();
+        let stale = DateTime::from_millis(now.timestamp_millis() - LAST_SEEN_RESOLUTION);
+        self.collection
+            .update_one(
+                doc! {
+                    "_id": session_oid,
+                    "$or": [
+                        {"lastSeenAt": {"$lt": stale}},
+                        {"lastSeenAt": {"$exists": false}},
+                    ],
+                },
+                doc! {"$set": {"lastSeenAt": now}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("sessions"),
+                message: String::from("Failure updating session."),
+            })
+    }
+}
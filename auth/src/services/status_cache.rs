@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Entries kept before the expired ones are dropped.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Account statuses checked by the extractors in strict mode, kept for a
+/// short time so each request does not hit the database. Clones share the
+/// entries.
+#[derive(Clone)]
+pub struct StatusCache {
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<String, (Instant, bool)>>>,
+}
+
+impl StatusCache {
+    pub fn new(ttl: Duration) -> StatusCache {
+        StatusCache {
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Whether the user was active, `None` when unknown or expired.
+    pub fn get(&self, user_id: &str) -> Option<bool> {
+        let entries = self.entries.lock().ok()?;
+        entries
+            .get(user_id)
+            .filter(|(checked_at, _)| checked_at.elapsed() < self.ttl)
+            .map(|(_, active)| *active)
+    }
+
+    pub fn insert(&self, user_id: &str, active: bool) {
+        if let Ok(mut entries) = self.entries.lock() {
+            if entries.len() >= PRUNE_THRESHOLD {
+                let ttl = self.ttl;
+                entries.retain(|_, (checked_at, _)| checked_at.elapsed() < ttl);
+            }
+            entries.insert(String::from(user_id), (Instant::now(), active));
+        }
+    }
+
+    pub fn invalidate(&self, user_id: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(user_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_cache() {
+        let cache = StatusCache::new(Duration::from_secs(60));
+        assert_eq!(cache.get("user"), None);
+        cache.insert("user", false);
+        assert_eq!(cache.clone().get("user"), Some(false));
+        cache.invalidate("user");
+        assert_eq!(cache.get("user"), None);
+
+        let expired = StatusCache::new(Duration::from_secs(0));
+        expired.insert("user", true);
+        assert_eq!(expired.get("user"), None);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::models::tokens::{ActionToken, ActionTokenKind};
+use error::SBError;
+use mongodb::bson::{doc, to_bson, DateTime};
+use mongodb::Collection;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+type ActionTokenServiceResult<T> = std::result::Result<T, SBError>;
+
+/// Lifetime of the tokens, in milliseconds.
+const EMAIL_VERIFICATION_DURATION: i64 = 24 * 3600 * 1000;
+const PASSWORD_RESET_DURATION: i64 = 3600 * 1000;
+const MAGIC_LINK_DURATION: i64 = 15 * 60 * 1000;
+
+#[derive(Clone)]
+pub struct ActionTokenService {
+    collection: Collection<ActionToken>,
+}
+
+impl ActionTokenService {
+    pub fn new(collection: Collection<ActionToken>) -> ActionTokenService {
+        ActionTokenService { collection }
+    }
+
+    fn hash_token(token: &str) -> String {
+        hex::encode(Sha256::digest(token.as_bytes()))
+    }
+
+    fn kind_filter(kind: ActionTokenKind) -> ActionTokenServiceResult<mongodb::bson::Bson> {
+        to_bson(&kind).map_err(|_| SBError::InternalServiceError {
+            service: String::from("tokens"),
+            message: String::from("Failure serializing token kind."),
+        })
+    }
+
+    /// Issues a token for the user, replacing their previous token of the
+    /// same kind.
+    pub async fn create(
+        &self,
+        user_id: &str,
+        email: &str,
+        kind: ActionTokenKind,
+    ) -> ActionTokenServiceResult<String> {
+        self.collection
+            .delete_many(
+                doc! {"userId": user_id, "kind": ActionTokenService::kind_filter(kind)?},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("tokens"),
+                message: String::from("Failure replacing token."),
+            })?;
+
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = hex::encode(bytes);
+        let duration = match kind {
+            ActionTokenKind::EmailVerification => EMAIL_VERIFICATION_DURATION,
+            ActionTokenKind::PasswordReset => PASSWORD_RESET_DURATION,
+            ActionTokenKind::MagicLink => MAGIC_LINK_DURATION,
+        };
+        let action_token = ActionToken {
+            id: None,
+            user_id: String::from(user_id),
+            email: String::from(email),
+            kind,
+            token_hash: ActionTokenService::hash_token(&token),
+            created_at: DateTime::now(),
+            expires_at: DateTime::from_millis(DateTime::now().timestamp_millis() + duration),
+        };
+        self.collection
+            .insert_one(action_token, None)
+            .await
+            .map(|_| token)
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("tokens"),
+                message: String::from("Could not create token."),
+            })
+    }
+
+    /// Deletes a valid token and returns it.
+    pub async fn consume(
+        &self,
+        token: &str,
+        kind: ActionTokenKind,
+    ) -> ActionTokenServiceResult<ActionToken> {
+        let consumed = self
+            .collection
+            .find_one_and_delete(
+                doc! {
+                    "tokenHash": ActionTokenService::hash_token(token),
+                    "kind": ActionTokenService::kind_filter(kind)?,
+                    "expiresAt": {"$gt": DateTime::now()},
+                },
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("tokens"),
+                message: String::from("Failure consuming token."),
+            })?;
+        match consumed {
+            Some(action_token) => Ok(action_token),
+            None => Err(SBError::ServiceError {
+                service: String::from("tokens"),
+                message: String::from("Invalid or expired token."),
+            }),
+        }
+    }
+}
@@ -1,19 +1,121 @@
-use crate::models::users::{Claims, UpdateUser, User};
-use bcrypt::{hash, verify};
-use chrono::prelude::*;
+use crate::models::oidc::{ExternalProfile, Identity};
+use crate::models::users::{AccountStatus, AdminUpdateUser, UpdateUser, User, UserPage};
+use crate::services::password_hashing::PasswordHashing;
+use crate::services::password_policy::PasswordPolicy;
 use error::SBError;
 use futures::TryStreamExt;
-use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
 use mongodb::bson::oid::ObjectId;
-use mongodb::bson::{doc, document::Document};
+use mongodb::bson::{doc, document::Document, to_bson, Bson};
 use mongodb::options::{FindOptions, UpdateModifications};
 use mongodb::results::InsertOneResult;
 use mongodb::Collection;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
+use tokio::task;
 
 type UserServiceResult<T> = std::result::Result<T, SBError>;
 
+/// Largest page returned by `UserService::search`.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// Last page index accepted by `UserService::search`.
+const MAX_PAGE: u64 = 10_000;
+
+/// Update merging `updates` into the metadata document `field`, null values
+/// remove their key.
+pub fn metadata_update(field: &str, updates: &Document) -> UserServiceResult<Document> {
+    let mut set = Document::new();
+    let mut unset = Document::new();
+    for (key, value) in updates {
+        if key.is_empty() || key.contains('.') || key.starts_with('$') {
+            return Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: format!("Invalid metadata key {}.", key),
+            });
+        }
+        match value {
+            Bson::Null => unset.insert(format!("{}.{}", field, key), ""),
+            value => set.insert(format!("{}.{}", field, key), value.clone()),
+        };
+    }
+    let mut update = Document::new();
+    if !set.is_empty() {
+        update.insert("$set", set);
+    }
+    if !unset.is_empty() {
+        update.insert("$unset", unset);
+    }
+    Ok(update)
+}
+
+/// Fields of the users that admin queries may filter and sort on. Secrets
+/// like the password hash are left out so they cannot be probed with
+/// filters such as `$regex`.
+const QUERY_FIELDS: &[&str] = &[
+    "_id",
+    "username",
+    "email",
+    "emailVerified",
+    "firstName",
+    "lastName",
+    "anonymous",
+    "status",
+    "passwordResetRequired",
+];
+
+fn invalid_query(message: String) -> SBError {
+    SBError::ServiceError {
+        service: String::from("users"),
+        message,
+    }
+}
+
+fn check_query_field(key: &str) -> UserServiceResult<()> {
+    let field = key.split('.').next().unwrap_or_default();
+    match QUERY_FIELDS.contains(&field) {
+        true => Ok(()),
+        false => Err(invalid_query(format!(
+            "Users cannot be queried on {}.",
+            key
+        ))),
+    }
+}
+
+/// Checks that a user filter only reads the fields of `QUERY_FIELDS`,
+/// through `$and`, `$or` and `$nor` included. Other top-level operators,
+/// like `$where` or `$expr`, are refused.
+pub fn check_user_filter(filter: &Document) -> UserServiceResult<()> {
+    for (key, value) in filter {
+        match key.as_str() {
+            "$and" | "$or" | "$nor" => match value {
+                Bson::Array(filters) => {
+                    for filter in filters {
+                        match filter {
+                            Bson::Document(filter) => check_user_filter(filter)?,
+                            _ => {
+                                return Err(invalid_query(format!(
+                                    "{} must hold filter documents.",
+                                    key
+                                )))
+                            }
+                        }
+                    }
+                }
+                _ => return Err(invalid_query(format!("{} must be an array.", key))),
+            },
+            key if key.starts_with('$') => {
+                return Err(invalid_query(format!(
+                    "The {} operator is not allowed.",
+                    key
+                )))
+            }
+            key => check_query_field(key)?,
+        }
+    }
+    Ok(())
+}
+
 #[derive(Deserialize, Debug, Serialize)]
 pub struct MarshalledInsertOne {
     pub _id: String,
@@ -22,31 +124,64 @@ pub struct MarshalledInsertOne {
 #[derive(Clone)]
 pub struct UserService {
     collection: Collection<User>,
+    pub password_policy: PasswordPolicy,
+    pub password_hashing: PasswordHashing,
 }
 
 impl UserService {
-    pub fn new(collection: Collection<User>) -> UserService {
-        UserService { collection }
+    pub fn new(
+        collection: Collection<User>,
+        password_policy: PasswordPolicy,
+        password_hashing: PasswordHashing,
+    ) -> UserService {
+        UserService {
+            collection,
+            password_policy,
+            password_hashing,
+        }
     }
 
-    pub fn hash_password(&self, password: &str) -> UserServiceResult<String> {
-        let cost = 4;
-        let hashed = hash(password, cost).map_err(|_| SBError::InternalServiceError {
-            service: String::from("users"),
-            message: String::from("Failure hashing password"),
-        })?;
-        Ok(hashed)
+    /// Hashes off the async runtime, hashing is slow by design.
+    pub async fn hash_password(&self, password: &str) -> UserServiceResult<String> {
+        let hashing = self.password_hashing;
+        let password = String::from(password);
+        task::spawn_blocking(move || hashing.hash(&password))
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure hashing password"),
+            })?
+    }
+
+    async fn verify_password(password: &str, hash: &str) -> UserServiceResult<bool> {
+        let password = String::from(password);
+        let hash = String::from(hash);
+        task::spawn_blocking(move || PasswordHashing::verify(&password, &hash))
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure verifying password."),
+            })?
     }
 
     pub async fn create(&self, user: User) -> UserServiceResult<MarshalledInsertOne> {
-        if user.password == None {
+        if user.password.is_none() {
             return Err(SBError::ServiceError {
                 service: String::from("users"),
                 message: String::from("No password provided"),
             });
         }
 
-        let to_insert = user.clone();
+        self.password_policy
+            .validate(user.password.as_deref().unwrap_or_default())?;
+
+        let mut to_insert = user.clone();
+        to_insert.email_verified = false;
+        to_insert.identities = vec![];
+        to_insert.anonymous = false;
+        to_insert.app_metadata = Document::new();
+        to_insert.status = AccountStatus::Active;
+        to_insert.password_reset_required = false;
 
         let check_username = self
             .collection
@@ -66,21 +201,21 @@ impl UserService {
                 message: String::from("No user found"),
             })?;
 
-        if !check_email.is_none() {
+        if check_email.is_some() {
             return Err(SBError::ServiceError {
                 service: String::from("users"),
                 message: String::from("Username already in use."),
             });
         }
 
-        if !check_username.is_none() {
+        if check_username.is_some() {
             return Err(SBError::ServiceError {
                 service: String::from("users"),
                 message: String::from("Username already in use."),
             });
         }
 
-        let hashed = self.hash_password(user.password.unwrap().as_ref())?;
+        let hashed = self.hash_password(user.password.unwrap().as_ref()).await?;
         self.collection
             .insert_one(to_insert.copy_with_hash(hashed), None)
             .await
@@ -93,6 +228,278 @@ impl UserService {
             })
     }
 
+    /// Creates a user signing in with an external identity, such users
+    /// have no password.
+    pub async fn create_external(&self, user: User) -> UserServiceResult<MarshalledInsertOne> {
+        let existing = self
+            .collection
+            .find_one(
+                doc! {"$or": [{"email": &user.email}, {"username": &user.username}]},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure finding user."),
+            })?;
+        if existing.is_some() {
+            return Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("Username already in use."),
+            });
+        }
+        self.collection
+            .insert_one(user.copy_without_hash(), None)
+            .await
+            .map(|r: InsertOneResult| MarshalledInsertOne {
+                _id: r.inserted_id.as_object_id().unwrap().to_hex(),
+            })
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Could not create user."),
+            })
+    }
+
+    pub async fn username_exists(&self, username: &str) -> UserServiceResult<bool> {
+        self.collection
+            .find_one(doc! {"username": username}, None)
+            .await
+            .map(|user| user.is_some())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure finding user."),
+            })
+    }
+
+    pub async fn get_by_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> UserServiceResult<Option<User>> {
+        self.collection
+            .find_one(
+                doc! {"identities": {"$elemMatch": {"provider": provider, "subject": subject}}},
+                None,
+            )
+            .await
+            .map(|user| user.map(|user| user.copy_without_hash()))
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure finding user."),
+            })
+    }
+
+    /// Adds an external identity to a user, an identity belongs to a single
+    /// user.
+    pub async fn link_identity(&self, user_id: &str, identity: Identity) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        if let Some(owner) = self
+            .get_by_identity(&identity.provider, &identity.subject)
+            .await?
+        {
+            return match owner.id == Some(user_oid) {
+                true => Ok(()),
+                false => Err(SBError::ServiceError {
+                    service: String::from("users"),
+                    message: String::from("Identity already linked to another user."),
+                }),
+            };
+        }
+        let identity =
+            mongodb::bson::to_bson(&identity).map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure serializing identity."),
+            })?;
+        self.collection
+            .update_one(
+                doc! {"_id": user_oid},
+                doc! {"$push": {"identities": identity}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure updating user."),
+            })
+    }
+
+    /// Creates a guest user without any credentials.
+    pub async fn create_anonymous(&self) -> UserServiceResult<MarshalledInsertOne> {
+        let mut bytes = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let user = User {
+            username: format!("anonymous-{}", hex::encode(bytes)),
+            anonymous: true,
+            ..Default::default()
+        };
+        self.collection
+            .insert_one(user, None)
+            .await
+            .map(|r: InsertOneResult| MarshalledInsertOne {
+                _id: r.inserted_id.as_object_id().unwrap().to_hex(),
+            })
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Could not create user."),
+            })
+    }
+
+    pub async fn email_in_use(&self, email: &str) -> UserServiceResult<bool> {
+        self.collection
+            .find_one(doc! {"email": email, "anonymous": {"$ne": true}}, None)
+            .await
+            .map(|user| user.is_some())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure finding user."),
+            })
+    }
+
+    /// Turns an anonymous user into a regular one, keeping its id.
+    async fn convert_anonymous(
+        &self,
+        user_id: &str,
+        mut fields: Document,
+    ) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        fields.insert("anonymous", false);
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": user_oid, "anonymous": true},
+                doc! {"$set": fields},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure updating user."),
+            })?;
+        match result.matched_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("User is not anonymous."),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gives an anonymous user an email and a password.
+    pub async fn upgrade(
+        &self,
+        user_id: &str,
+        email: &str,
+        password: &str,
+        username: Option<&str>,
+    ) -> UserServiceResult<()> {
+        self.password_policy.validate(password)?;
+        if self.email_in_use(email).await? {
+            return Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("Username already in use."),
+            });
+        }
+        let mut fields = doc! {"email": email, "emailVerified": false};
+        if let Some(username) = username {
+            if self.username_exists(username).await? {
+                return Err(SBError::ServiceError {
+                    service: String::from("users"),
+                    message: String::from("Username already in use."),
+                });
+            }
+            fields.insert("username", username);
+        }
+        fields.insert("password", self.hash_password(password).await?);
+        self.convert_anonymous(user_id, fields).await
+    }
+
+    /// Fills an anonymous user with the profile of an external identity.
+    pub async fn upgrade_with_profile(
+        &self,
+        user_id: &str,
+        profile: &ExternalProfile,
+    ) -> UserServiceResult<()> {
+        let email = match &profile.email {
+            Some(email) if !self.email_in_use(email).await? => email,
+            Some(_) => {
+                return Err(SBError::ServiceError {
+                    service: String::from("users"),
+                    message: String::from("Username already in use."),
+                })
+            }
+            None => {
+                return Err(SBError::ServiceError {
+                    service: String::from("users"),
+                    message: String::from("The provider did not share an email address."),
+                })
+            }
+        };
+        self.convert_anonymous(
+            user_id,
+            doc! {
+                "email": email,
+                "emailVerified": profile.email_verified,
+                "firstName": profile.first_name.clone().unwrap_or_default(),
+                "lastName": profile.last_name.clone().unwrap_or_default(),
+            },
+        )
+        .await
+    }
+
+    async fn update_metadata(
+        &self,
+        user_id: &str,
+        field: &str,
+        updates: &Document,
+    ) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        let update = metadata_update(field, updates)?;
+        if update.is_empty() {
+            return Ok(());
+        }
+        let result = self
+            .collection
+            .update_one(doc! {"_id": user_oid}, update, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure updating user."),
+            })?;
+        match result.matched_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("No user found"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn update_app_metadata(
+        &self,
+        user_id: &str,
+        updates: &Document,
+    ) -> UserServiceResult<()> {
+        self.update_metadata(user_id, "appMetadata", updates).await
+    }
+
+    pub async fn update_user_metadata(
+        &self,
+        user_id: &str,
+        updates: &Document,
+    ) -> UserServiceResult<()> {
+        self.update_metadata(user_id, "userMetadata", updates).await
+    }
+
     pub async fn get(&self, user_id: &str) -> UserServiceResult<User> {
         let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
             service: String::from("users"),
@@ -115,6 +522,24 @@ impl UserService {
         }
     }
 
+    pub async fn get_by_email(&self, email: &str) -> UserServiceResult<User> {
+        let res = self
+            .collection
+            .find_one(doc! {"email":email, "anonymous": {"$ne": true}}, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure finding user."),
+            })?;
+        match res {
+            Some(r) => Ok(r.copy_without_hash()),
+            None => Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("No user found"),
+            }),
+        }
+    }
+
     pub async fn update(&self, user_id: &str, updates: UpdateUser) -> UserServiceResult<Document> {
         let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
             service: String::from("users"),
@@ -123,12 +548,21 @@ impl UserService {
 
         let mut updates_doc = Document::new();
 
-        if updates.email.is_some() {
-            updates_doc.insert("email", updates.email.unwrap());
+        if let Some(email) = updates.email {
+            updates_doc.insert("email", email);
+            updates_doc.insert("emailVerified", false);
         }
 
-        if updates.username.is_some() {
-            updates_doc.insert("username", updates.username.unwrap());
+        if let Some(username) = updates.username {
+            updates_doc.insert("username", username);
+        }
+
+        if let Some(first_name) = updates.first_name {
+            updates_doc.insert("firstName", first_name);
+        }
+
+        if let Some(last_name) = updates.last_name {
+            updates_doc.insert("lastName", last_name);
         }
 
         let mods = UpdateModifications::Document(doc! {"$set":updates_doc});
@@ -147,6 +581,191 @@ impl UserService {
         }
     }
 
+    pub async fn set_email_verified(&self, user_id: &str) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        self.collection
+            .update_one(
+                doc! {"_id": user_oid},
+                doc! {"$set": {"emailVerified": true}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure updating user."),
+            })
+    }
+
+    /// Replaces the password of a user, the new one has to follow the
+    /// password policy.
+    pub async fn set_password(&self, user_id: &str, password: &str) -> UserServiceResult<()> {
+        self.password_policy.validate(password)?;
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        let hashed = self.hash_password(password).await?;
+        self.set_fields(
+            user_oid,
+            doc! {"password": hashed, "passwordResetRequired": false},
+        )
+        .await
+    }
+
+    async fn set_fields(&self, user_oid: ObjectId, fields: Document) -> UserServiceResult<()> {
+        let result = self
+            .collection
+            .update_one(doc! {"_id": user_oid}, doc! {"$set": fields}, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure updating user."),
+            })?;
+        match result.matched_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("No user found"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn set_status(&self, user_id: &str, status: &AccountStatus) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        let status = to_bson(status).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure serializing status."),
+        })?;
+        self.set_fields(user_oid, doc! {"status": status}).await
+    }
+
+    /// Refuses password logins until the password is set again.
+    pub async fn require_password_reset(&self, user_id: &str) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        self.set_fields(user_oid, doc! {"passwordResetRequired": true})
+            .await
+    }
+
+    /// Applies the changes of an admin, the new email or username must not
+    /// belong to another user.
+    pub async fn admin_update(
+        &self,
+        user_id: &str,
+        updates: AdminUpdateUser,
+    ) -> UserServiceResult<Document> {
+        let user = self.get(user_id).await?;
+        let email_taken = match &updates.email {
+            Some(email) if *email != user.email => self.email_in_use(email).await?,
+            _ => false,
+        };
+        let username_taken = match &updates.username {
+            Some(username) if *username != user.username => self.username_exists(username).await?,
+            _ => false,
+        };
+        if email_taken || username_taken {
+            return Err(SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("Username already in use."),
+            });
+        }
+        let email_verified = updates.email_verified;
+        let email_changed = matches!(&updates.email, Some(email) if *email != user.email);
+        let updates = UpdateUser {
+            username: updates.username,
+            email: updates.email.filter(|_| email_changed),
+            first_name: updates.first_name,
+            last_name: updates.last_name,
+        };
+        let has_updates = updates.username.is_some()
+            || updates.email.is_some()
+            || updates.first_name.is_some()
+            || updates.last_name.is_some();
+        if has_updates {
+            self.update(user_id, updates).await?;
+        }
+        if let Some(email_verified) = email_verified {
+            self.set_fields(user.id.unwrap(), doc! {"emailVerified": email_verified})
+                .await?;
+        }
+        Ok(doc! {"success": true})
+    }
+
+    /// Pages through the users, `query` matches the start of the email,
+    /// username or names, case-insensitively.
+    pub async fn search(
+        &self,
+        query: Option<&str>,
+        page: u64,
+        per_page: i64,
+    ) -> UserServiceResult<UserPage> {
+        let per_page = per_page.clamp(1, MAX_PAGE_SIZE);
+        let skip = page
+            .checked_mul(per_page as u64)
+            .filter(|_| page <= MAX_PAGE)
+            .ok_or_else(|| SBError::ServiceError {
+                service: String::from("users"),
+                message: format!("The page must be at most {}.", MAX_PAGE),
+            })?;
+        let filter = match query.map(str::trim).filter(|query| !query.is_empty()) {
+            Some(query) => {
+                let pattern = format!("^{}", regex::escape(query));
+                let regex = doc! {"$regex": pattern, "$options": "i"};
+                doc! {"$or": [
+                    {"email": regex.clone()},
+                    {"username": regex.clone()},
+                    {"firstName": regex.clone()},
+                    {"lastName": regex},
+                ]}
+            }
+            None => Document::new(),
+        };
+        let total = self
+            .collection
+            .count_documents(filter.clone(), None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure listing users."),
+            })?;
+        let options = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .skip(skip)
+            .limit(per_page)
+            .build();
+        let users = self.get_users(Some(filter), Some(options)).await?;
+        Ok(UserPage {
+            users,
+            total,
+            page,
+            per_page,
+        })
+    }
+
+    async fn store_hash(&self, user_oid: ObjectId, hashed: String) -> UserServiceResult<()> {
+        self.collection
+            .update_one(
+                doc! {"_id": user_oid},
+                doc! {"$set": {"password": hashed}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure updating user."),
+            })
+    }
+
     pub async fn delete(&self, user_id: &str) -> UserServiceResult<Document> {
         let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
             service: String::from("users"),
@@ -163,17 +782,16 @@ impl UserService {
             .map(|_| doc! {"success":true})
     }
 
-    pub async fn authenticate(
-        &self,
-        email: &str,
-        password: &str,
-        secret: String,
-    ) -> UserServiceResult<Document> {
-        let res = self.collection.find_one(doc! { "email":email}, None).await;
-        match res.clone() {
-            Err(e) => println!("{}", e),
-            _ => (),
-        };
+    /// Checks the credentials of a user, tokens are issued by
+    /// `AuthenticationService::authenticate`.
+    pub async fn verify_credentials(&self, email: &str, password: &str) -> UserServiceResult<User> {
+        let res = self
+            .collection
+            .find_one(doc! { "email":email, "anonymous": {"$ne": true}}, None)
+            .await;
+        if let Err(e) = res.clone() {
+            println!("{}", e);
+        }
         if res.is_err() {
             return Err(SBError::InternalServiceError {
                 service: String::from("users"),
@@ -190,28 +808,39 @@ impl UserService {
         }
 
         let user = user_opt.unwrap();
-        let hash = user.password;
-        if hash == None {
+        UserService::check_password(&user, password).await?;
+        self.rehash_if_outdated(&user, password).await;
+        Ok(user.copy_without_hash())
+    }
+
+    /// Replaces a hash made with outdated parameters, the password is only
+    /// known at login.
+    async fn rehash_if_outdated(&self, user: &User, password: &str) {
+        let outdated = match (&user.id, &user.password) {
+            (Some(id), Some(hash)) if self.password_hashing.needs_rehash(hash) => *id,
+            _ => return,
+        };
+        let result = match self.hash_password(password).await {
+            Ok(hashed) => self.store_hash(outdated, hashed).await,
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            println!("{}", error);
+        }
+    }
+
+    async fn check_password(user: &User, password: &str) -> UserServiceResult<()> {
+        let hash = user.password.clone();
+        if hash.is_none() {
             return Err(SBError::ServiceError {
                 service: String::from("users"),
                 message: String::from("Passwordless user."),
             });
         }
-        let password_ok = verify(password, hash.unwrap().as_ref()).unwrap();
+        let password_ok = UserService::verify_password(password, &hash.unwrap()).await?;
 
         match password_ok {
-            true => {
-                let my_claims = Claims {
-                    sub: user.id.unwrap().to_hex(),
-                    exp: Utc::now().timestamp() as usize + 172800,
-                };
-                let h = Header::new(Algorithm::HS256);
-                let key = EncodingKey::from_secret(secret.as_ref());
-
-                let token = encode(&h, &my_claims, &key).unwrap();
-                let d = doc! {"token":token, "success":true};
-                Ok(d)
-            }
+            true => Ok(()),
             _ => Err(SBError::ServiceError {
                 service: String::from("users"),
                 message: String::from("Authentication failed."),
@@ -219,11 +848,61 @@ impl UserService {
         }
     }
 
+    /// Sets a new password after checking the current one.
+    pub async fn change_password(
+        &self,
+        user_id: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> UserServiceResult<()> {
+        let user_oid = ObjectId::from_str(user_id).map_err(|_| SBError::InternalServiceError {
+            service: String::from("users"),
+            message: String::from("Failure making oid object."),
+        })?;
+        let user = self
+            .collection
+            .find_one(doc! {"_id": user_oid}, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("users"),
+                message: String::from("Failure finding user."),
+            })?
+            .ok_or_else(|| SBError::ServiceError {
+                service: String::from("users"),
+                message: String::from("No user found"),
+            })?;
+        UserService::check_password(&user, current_password).await?;
+        self.set_password(user_id, new_password).await
+    }
+
+    /// Lists the users matching `filter`, which is checked with
+    /// `check_user_filter`. Only the sort, skip and limit of `options` are
+    /// kept, the sort is restricted to the same fields.
     pub async fn get_users(
         &self,
         filter: Option<Document>,
         options: Option<FindOptions>,
     ) -> UserServiceResult<Vec<User>> {
+        if let Some(filter) = &filter {
+            check_user_filter(filter)?;
+        }
+        let options = match options {
+            Some(options) => {
+                if let Some(sort) = &options.sort {
+                    for (key, _) in sort {
+                        check_query_field(key)?;
+                    }
+                }
+                Some(
+                    FindOptions::builder()
+                        .sort(options.sort)
+                        .skip(options.skip)
+                        .limit(options.limit)
+                        .build(),
+                )
+            }
+            None => None,
+        };
         let cursor = self.collection.find(filter, options).await.map_err(|_| {
             SBError::InternalServiceError {
                 service: String::from("users"),
@@ -231,6 +910,7 @@ impl UserService {
             }
         })?;
         cursor
+            .map_ok(|user| user.copy_without_hash())
             .try_collect::<Vec<User>>()
             .await
             .map_err(|_| SBError::InternalServiceError {
@@ -239,3 +919,37 @@ impl UserService {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_update() {
+        assert_eq!(
+            metadata_update("userMetadata", &doc! {"theme": "dark", "avatar": null}).unwrap(),
+            doc! {
+                "$set": {"userMetadata.theme": "dark"},
+                "$unset": {"userMetadata.avatar": ""},
+            }
+        );
+        assert!(metadata_update("appMetadata", &doc! {"roles.0": "admin"}).is_err());
+        assert!(metadata_update("appMetadata", &doc! {"$where": "1"}).is_err());
+    }
+
+    #[test]
+    fn test_check_user_filter() {
+        assert!(check_user_filter(&doc! {
+            "email": {"$regex": "^a"},
+            "status.state": "active",
+            "$or": [{"username": "a"}, {"$and": [{"firstName": "b"}]}],
+        })
+        .is_ok());
+        assert!(check_user_filter(&doc! {"password": {"$regex": "^\\$2b"}}).is_err());
+        assert!(check_user_filter(&doc! {"$or": [{"email": "a"}, {"password": "b"}]}).is_err());
+        assert!(check_user_filter(&doc! {"$nor": [{"$and": [{"password.0": "b"}]}]}).is_err());
+        assert!(check_user_filter(&doc! {"$where": "this.password"}).is_err());
+        assert!(check_user_filter(&doc! {"$expr": {"$eq": ["$password", ""]}}).is_err());
+        assert!(check_user_filter(&doc! {"$or": {"password": "a"}}).is_err());
+    }
+}
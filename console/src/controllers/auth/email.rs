@@ -0,0 +1,61 @@
+use crate::models::mail::MailLinks;
+use auth::mailer::Mailer;
+use auth::models::tokens::VerifyEmailRequest;
+use auth::models::users::AuthorizedUser;
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use error::SBError;
+use web::Json;
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/email");
+
+    resource
+        .route("/verify/send", web::post().to(send_verification))
+        .route("/verify", web::post().to(verify_email))
+}
+
+async fn send_verification(
+    service: web::Data<AuthenticationService>,
+    mailer: web::Data<dyn Mailer>,
+    links: web::Data<MailLinks>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .request_email_verification(
+            &authorized_user.sub,
+            mailer.as_ref(),
+            links.verify_email.as_deref(),
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn verify_email(
+    service: web::Data<AuthenticationService>,
+    query: Json<VerifyEmailRequest>,
+) -> impl Responder {
+    let result = service.verify_email(&query.token).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use auth::models::sessions::ClientInfo;
 use auth::models::users::AuthenticateUser;
 use auth::services::AuthenticationService;
 
@@ -14,11 +15,10 @@ pub fn get_service() -> Resource {
 async fn authenticate_user(
     service: web::Data<AuthenticationService>,
     user: Json<AuthenticateUser>,
+    client: ClientInfo,
 ) -> impl Responder {
-    let private_key = (*service.secret).to_owned();
     let result = service
-        .users
-        .authenticate(&user.email, &user.password, private_key)
+        .authenticate(&user.email, &user.password, None, &client)
         .await;
     match result {
         Ok(result) => HttpResponse::Ok().json(result),
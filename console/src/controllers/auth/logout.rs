@@ -0,0 +1,29 @@
+use auth::models::users::AuthorizedUser;
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Resource, Responder};
+use error::SBError;
+
+pub fn get_service() -> Resource {
+    let resource = web::resource("/logout");
+
+    resource.route(web::post().to(logout_user))
+}
+
+async fn logout_user(
+    service: web::Data<AuthenticationService>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service.logout(&authorized_user.sid).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
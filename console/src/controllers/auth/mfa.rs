@@ -0,0 +1,95 @@
+use auth::models::mfa::{MfaCodeRequest, MfaVerifyRequest};
+use auth::models::sessions::ClientInfo;
+use auth::models::users::AuthorizedUser;
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use error::SBError;
+use web::Json;
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/mfa");
+
+    resource
+        .route("/enroll", web::post().to(enroll))
+        .route("/confirm", web::post().to(confirm))
+        .route("/disable", web::post().to(disable))
+        .route("/verify", web::post().to(verify))
+}
+
+async fn enroll(
+    service: web::Data<AuthenticationService>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service.enroll_mfa(&authorized_user.sub).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn confirm(
+    service: web::Data<AuthenticationService>,
+    query: Json<MfaCodeRequest>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service.confirm_mfa(&authorized_user.sub, &query.code).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn disable(
+    service: web::Data<AuthenticationService>,
+    query: Json<MfaCodeRequest>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service.disable_mfa(&authorized_user.sub, &query.code).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn verify(
+    service: web::Data<AuthenticationService>,
+    query: Json<MfaVerifyRequest>,
+    client: ClientInfo,
+) -> impl Responder {
+    let result = service
+        .verify_mfa(&query.mfa_token, &query.code, None, &client)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
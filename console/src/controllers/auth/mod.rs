@@ -1,16 +1,32 @@
 use actix_web::{web, Scope};
 
+mod email;
 mod login;
+mod logout;
+mod mfa;
+mod password;
 mod profile;
+mod refresh;
 mod registration;
+mod sessions;
+mod status;
+mod unlock;
 mod users;
 
 pub fn get_service() -> Scope {
     let resource = web::scope("/auth");
 
     resource
+        .service(status::get_service())
         .service(users::get_service())
         .service(profile::get_service())
         .service(registration::get_service())
         .service(login::get_service())
+        .service(refresh::get_service())
+        .service(logout::get_service())
+        .service(sessions::get_service())
+        .service(email::get_service())
+        .service(password::get_service())
+        .service(unlock::get_service())
+        .service(mfa::get_service())
 }
@@ -0,0 +1,95 @@
+use crate::models::mail::MailLinks;
+use auth::mailer::Mailer;
+use auth::models::tokens::{EmailRequest, ResetPasswordRequest};
+use auth::models::users::{AuthorizedUser, ChangePasswordRequest};
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use error::SBError;
+use mongodb::bson::doc;
+use web::Json;
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/password");
+
+    resource
+        .route("/reset/request", web::post().to(request_reset))
+        .route("/reset", web::post().to(reset_password))
+        .route("/change", web::post().to(change_password))
+}
+
+async fn request_reset(
+    service: web::Data<AuthenticationService>,
+    mailer: web::Data<dyn Mailer>,
+    links: web::Data<MailLinks>,
+    query: Json<EmailRequest>,
+) -> impl Responder {
+    let result = service
+        .request_password_reset(
+            &query.email,
+            mailer.as_ref(),
+            links.reset_password.as_deref(),
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn reset_password(
+    service: web::Data<AuthenticationService>,
+    query: Json<ResetPasswordRequest>,
+) -> impl Responder {
+    let result = service.reset_password(&query.token, &query.password).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn change_password(
+    service: web::Data<AuthenticationService>,
+    query: Json<ChangePasswordRequest>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .change_password(
+            &authorized_user.sub,
+            &authorized_user.sid,
+            &query.current_password,
+            &query.new_password,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
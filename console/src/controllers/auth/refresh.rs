@@ -0,0 +1,30 @@
+use auth::models::sessions::RefreshTokenRequest;
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Resource, Responder};
+use error::SBError;
+use web::Json;
+
+pub fn get_service() -> Resource {
+    let resource = web::resource("/refresh");
+
+    resource.route(web::post().to(refresh_token))
+}
+
+async fn refresh_token(
+    service: web::Data<AuthenticationService>,
+    query: Json<RefreshTokenRequest>,
+) -> impl Responder {
+    let result = service.refresh(&query.refresh_token, None).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
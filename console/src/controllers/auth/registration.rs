@@ -1,8 +1,11 @@
+use crate::models::mail::MailLinks;
+use auth::mailer::Mailer;
 use auth::models::users::User;
 use auth::services::AuthenticationService;
 
 use actix_web::{http, web, HttpResponse, Resource, Responder};
 use error::SBError;
+use mongodb::bson::doc;
 use web::Json;
 
 pub fn get_service() -> Resource {
@@ -13,11 +16,28 @@ pub fn get_service() -> Resource {
 
 async fn signup_user(
     service: web::Data<AuthenticationService>,
+    mailer: web::Data<dyn Mailer>,
+    links: web::Data<MailLinks>,
     user: Json<User>,
 ) -> impl Responder {
     let result = service.users.create(user.into_inner()).await;
     match result {
-        Ok(result) => HttpResponse::Ok().json(result),
+        Ok(result) => {
+            let verification = service
+                .request_email_verification(
+                    &result._id,
+                    mailer.as_ref(),
+                    links.verify_email.as_deref(),
+                )
+                .await;
+            if let Err(error) = verification {
+                println!("{}", error);
+            }
+            HttpResponse::Ok().json(result)
+        }
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
         Err(SBError::ServiceError {
             message,
             service: _,
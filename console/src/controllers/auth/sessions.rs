@@ -0,0 +1,81 @@
+use auth::models::users::AuthorizedUser;
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use error::SBError;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct SessionPath {
+    pub session_id: String,
+}
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/sessions");
+
+    resource
+        .route("", web::get().to(list_sessions))
+        .route("/revoke-others", web::post().to(revoke_other_sessions))
+        .route("/{session_id}/revoke", web::post().to(revoke_session))
+}
+
+async fn list_sessions(
+    service: web::Data<AuthenticationService>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .list_sessions(&authorized_user.sub, &authorized_user.sid)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn revoke_session(
+    service: web::Data<AuthenticationService>,
+    info: web::Path<SessionPath>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .revoke_session(&authorized_user.sub, &info.session_id)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn revoke_other_sessions(
+    service: web::Data<AuthenticationService>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .revoke_other_sessions(&authorized_user.sub, &authorized_user.sid)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::models::admin::ConsoleAdmin;
+use auth::models::users::{AccountStatusRequest, Info};
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Resource, Responder};
+use error::SBError;
+use web::Json;
+
+pub fn get_service() -> Resource {
+    let resource = web::resource("/users/{user_id}/status");
+
+    resource.route(web::post().to(set_account_status))
+}
+
+async fn set_account_status(
+    service: web::Data<AuthenticationService>,
+    info: web::Path<Info>,
+    query: Json<AccountStatusRequest>,
+    admin: ConsoleAdmin,
+) -> impl Responder {
+    let result = match query.to_status() {
+        Ok(status) => service.set_status(&info.user_id, &status).await,
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => {
+            println!(
+                "Account {} set to {:?} by {}",
+                info.user_id, query.state, admin.sub
+            );
+            HttpResponse::Ok().json(result)
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
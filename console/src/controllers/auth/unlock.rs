@@ -0,0 +1,35 @@
+use crate::models::admin::ConsoleAdmin;
+use auth::models::login_attempts::UnlockRequest;
+use auth::services::AuthenticationService;
+
+use actix_web::{http, web, HttpResponse, Resource, Responder};
+use error::SBError;
+use web::Json;
+
+pub fn get_service() -> Resource {
+    let resource = web::resource("/unlock");
+
+    resource.route(web::post().to(unlock_account))
+}
+
+async fn unlock_account(
+    service: web::Data<AuthenticationService>,
+    query: Json<UnlockRequest>,
+    admin: ConsoleAdmin,
+) -> impl Responder {
+    let result = service.unlock(&query.email).await;
+    match result {
+        Ok(result) => {
+            println!("Account {} unlocked by {}", query.email, admin.sub);
+            HttpResponse::Ok().json(result)
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
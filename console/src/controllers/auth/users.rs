@@ -20,7 +20,7 @@ async fn update_user(
     updates: Json<UpdateUser>,
     authorized_user: AuthorizedUser,
 ) -> impl Responder {
-    if &authorized_user.sub != &info.user_id {
+    if authorized_user.sub != info.user_id {
         return HttpResponse::Unauthorized().finish();
     }
 
@@ -46,7 +46,7 @@ async fn get_single_user(
     info: web::Path<Info>,
     authorized_user: AuthorizedUser,
 ) -> impl Responder {
-    if &authorized_user.sub != &info.user_id {
+    if authorized_user.sub != info.user_id {
         return HttpResponse::Unauthorized().finish();
     }
 
@@ -70,7 +70,7 @@ async fn delete_single_user(
     info: web::Path<Info>,
     authorized_user: AuthorizedUser,
 ) -> impl Responder {
-    if &authorized_user.sub != &info.user_id {
+    if authorized_user.sub != info.user_id {
         return HttpResponse::Unauthorized().finish();
     }
 
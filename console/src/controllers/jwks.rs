@@ -0,0 +1,24 @@
+use crate::services::project_auth::ProjectAuthService;
+use actix_web::{web, HttpResponse, Resource, Responder};
+use auth::services::AuthenticationService;
+
+pub fn get_service() -> Resource {
+    web::resource("/.well-known/jwks.json").route(web::get().to(get_jwks))
+}
+
+/// Public keys verifying both console and project tokens.
+async fn get_jwks(
+    auth_service: web::Data<AuthenticationService>,
+    project_auth_service: web::Data<ProjectAuthService>,
+) -> impl Responder {
+    let mut jwks = auth_service.keys.jwks();
+    for key in project_auth_service.keys.jwks().keys {
+        if jwks
+            .find(key.common.key_id.as_deref().unwrap_or_default())
+            .is_none()
+        {
+            jwks.keys.push(key);
+        }
+    }
+    HttpResponse::Ok().json(jwks)
+}
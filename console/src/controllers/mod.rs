@@ -2,6 +2,7 @@ use actix_web::{web, Scope};
 
 mod auth;
 pub mod console;
+pub mod jwks;
 mod projects;
 
 pub fn get_service() -> Scope {
@@ -0,0 +1,98 @@
+use crate::models::api_key::ApiKeyKind;
+use crate::models::project::{AdminAccess, ProjectUser};
+use crate::services::projects::ProjectService;
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use error::SBError;
+use serde::Deserialize;
+use web::Json;
+
+#[derive(Deserialize)]
+struct ProjectInfo {
+    pub project_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectApiKeyInfo {
+    pub project_id: String,
+    pub key_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectCreateApiKeyQuery {
+    pub name: String,
+    pub kind: ApiKeyKind,
+}
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/keys/{project_id}");
+
+    resource
+        .route("/list", web::get().to(get_api_keys))
+        .route("/create", web::post().to(create_api_key))
+        .route("/{key_id}/revoke", web::post().to(revoke_api_key))
+        .route("/{key_id}/rotate", web::post().to(rotate_api_key))
+}
+
+async fn get_api_keys(authorized_user: ProjectUser<AdminAccess>) -> impl Responder {
+    HttpResponse::Ok().json(&authorized_user.project.api_keys)
+}
+
+async fn create_api_key(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<ProjectCreateApiKeyQuery>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service
+        .create_api_key(&info.project_id, &query.name, query.kind)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn revoke_api_key(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectApiKeyInfo>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service.revoke_api_key(&info.project_id, &info.key_id).await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn rotate_api_key(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectApiKeyInfo>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service.rotate_api_key(&info.project_id, &info.key_id).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
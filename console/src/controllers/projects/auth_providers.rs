@@ -0,0 +1,74 @@
+use crate::models::project::{AdminAccess, ProjectUser};
+use crate::services::projects::ProjectService;
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use auth::models::oidc::OidcProvider;
+use error::SBError;
+use serde::Deserialize;
+use web::Json;
+
+#[derive(Deserialize)]
+struct ProjectInfo {
+    pub project_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectAuthProviderInfo {
+    pub project_id: String,
+    pub name: String,
+}
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/auth-providers/{project_id}");
+
+    resource
+        .route("/list", web::get().to(get_auth_providers))
+        .route("/set", web::post().to(set_auth_provider))
+        .route("/{name}/remove", web::post().to(remove_auth_provider))
+}
+
+async fn get_auth_providers(authorized_user: ProjectUser<AdminAccess>) -> impl Responder {
+    HttpResponse::Ok().json(&authorized_user.project.auth_providers)
+}
+
+async fn set_auth_provider(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<OidcProvider>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service
+        .set_auth_provider(&info.project_id, query.into_inner())
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn remove_auth_provider(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectAuthProviderInfo>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service
+        .remove_auth_provider(&info.project_id, &info.name)
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
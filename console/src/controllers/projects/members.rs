@@ -0,0 +1,199 @@
+use crate::models::project::{OwnerAccess, ProjectMemberProfile, ProjectRole, ProjectUser};
+use crate::services::projects::ProjectService;
+use actix_web::{http, web, HttpResponse, Responder, Scope};
+use auth::models::users::AuthorizedUser;
+use auth::services::AuthenticationService;
+use error::SBError;
+use serde::Deserialize;
+use web::Json;
+
+#[derive(Deserialize)]
+struct ProjectInfo {
+    pub project_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectMemberInfo {
+    pub project_id: String,
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectInviteQuery {
+    pub email: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Deserialize)]
+struct ProjectMemberRoleQuery {
+    pub role: ProjectRole,
+}
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/members");
+
+    resource
+        .route("/invitations", web::get().to(get_invitations))
+        .route("/{project_id}/list", web::get().to(get_members))
+        .route("/{project_id}/invite", web::post().to(invite_member))
+        .route("/{project_id}/accept", web::post().to(accept_invitation))
+        .route("/{project_id}/decline", web::post().to(decline_invitation))
+        .route(
+            "/{project_id}/{user_id}/role",
+            web::post().to(set_member_role),
+        )
+        .route(
+            "/{project_id}/{user_id}/remove",
+            web::post().to(remove_member),
+        )
+}
+
+async fn get_invitations(
+    service: web::Data<ProjectService>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service.get_invitations_for_user(&authorized_user.sub).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_members(
+    auth_service: web::Data<AuthenticationService>,
+    authorized_user: ProjectUser,
+) -> impl Responder {
+    let mut members = vec![];
+    for member in authorized_user.project.users.iter() {
+        members.push(ProjectMemberProfile {
+            user_id: member.user_id.clone(),
+            role: member.role,
+            profile: auth_service.users.get(&member.user_id).await.ok(),
+        });
+    }
+    HttpResponse::Ok().json(members)
+}
+
+async fn invite_member(
+    service: web::Data<ProjectService>,
+    auth_service: web::Data<AuthenticationService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<ProjectInviteQuery>,
+    _authorized_user: ProjectUser<OwnerAccess>,
+) -> impl Responder {
+    let result = match auth_service.users.get_by_email(&query.email).await {
+        Ok(user) => {
+            service
+                .invite_user(
+                    &info.project_id,
+                    &user.id.map(|id| id.to_hex()).unwrap_or_default(),
+                    query.role,
+                )
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    // Unknown emails and users already member or invited get the same answer
+    // as a new invitation, so this cannot be used to probe accounts.
+    match result {
+        Ok(_) | Err(SBError::ServiceError { .. }) => HttpResponse::Ok().json(true),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn accept_invitation(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .accept_invitation(&info.project_id, &authorized_user.sub)
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn decline_invitation(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .decline_invitation(&info.project_id, &authorized_user.sub)
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn set_member_role(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectMemberInfo>,
+    query: Json<ProjectMemberRoleQuery>,
+    _authorized_user: ProjectUser<OwnerAccess>,
+) -> impl Responder {
+    let result = service
+        .set_member_role(&info.project_id, &info.user_id, query.role)
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn remove_member(
+    service: web::Data<ProjectService>,
+    info: web::Path<ProjectMemberInfo>,
+    authorized_user: ProjectUser,
+) -> impl Responder {
+    if authorized_user.role != ProjectRole::Owner && authorized_user.sub != info.user_id {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service.remove_member(&info.project_id, &info.user_id).await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
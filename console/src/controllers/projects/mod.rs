@@ -1,5 +1,8 @@
 use actix_web::{web, Scope};
 
+mod api_keys;
+mod auth_providers;
+mod members;
 mod read;
 mod services;
 mod write;
@@ -11,4 +14,7 @@ pub fn get_service() -> Scope {
         .service(write::get_service())
         .service(read::get_service())
         .service(services::get_service())
+        .service(members::get_service())
+        .service(api_keys::get_service())
+        .service(auth_providers::get_service())
 }
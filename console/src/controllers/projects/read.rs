@@ -44,7 +44,7 @@ async fn get_project(
     let result: std::result::Result<Project, SBError> = service.get(&info.project_id).await;
     match result {
         Ok(result) => {
-            if !result.users.contains(&authorized_user.sub) {
+            if result.get_role(&authorized_user.sub).is_none() {
                 return HttpResponse::Unauthorized().finish();
             }
             HttpResponse::Ok().json(result)
@@ -1,16 +1,53 @@
-use crate::models::project::ProjectUser;
+use crate::models::project::{ProjectEndUser, ProjectRole};
+use crate::models::rules::RuleCaller;
 use crate::services::project_auth::ProjectAuthService;
+use crate::services::projects::ProjectService;
 use actix_web::{http, web, HttpResponse, Responder, Scope};
+use auth::mailer::Mailer;
+use auth::models::login_attempts::UnlockRequest;
+use auth::models::oidc::{OidcCallbackRequest, OidcStartRequest};
+use auth::models::sessions::{ClientInfo, RefreshTokenRequest};
+use auth::models::tokens::{
+    EmailRequest, MagicLinkRequest, ResetPasswordRequest, VerifyEmailRequest,
+};
+use auth::models::users::{
+    AccountStatusRequest, AdminUpdateUser, AuthenticateUser, ChangePasswordRequest, UpgradeUser,
+    User, UserSearchQuery,
+};
 use error::SBError;
-use mongodb::{bson::Document, options::FindOptions};
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+};
 use serde::Deserialize;
 use web::Json;
 
+/// Page size of the user search when the request has none.
+const DEFAULT_PAGE_SIZE: i64 = 20;
+
 #[derive(Deserialize)]
 struct ProjectInfo {
     pub project_id: String,
 }
 
+#[derive(Deserialize)]
+struct ProjectUserInfo {
+    pub project_id: String,
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectSessionInfo {
+    pub project_id: String,
+    pub session_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectProviderInfo {
+    pub project_id: String,
+    pub provider: String,
+}
+
 #[derive(Deserialize)]
 struct ProjectAuthUserListQuery {
     pub filter: Option<Document>,
@@ -19,15 +56,62 @@ struct ProjectAuthUserListQuery {
 
 pub fn get_service() -> Scope {
     let resource = web::scope("/auth");
-    resource.route("/users/get", web::post().to(get_users))
+    resource
+        .route("/users/get", web::post().to(get_users))
+        .route("/users/unlock", web::post().to(unlock_user))
+        .route("/users/search", web::post().to(search_users))
+        .route("/users/create", web::post().to(create_user))
+        .route("/users/{user_id}", web::get().to(get_user))
+        .route("/users/{user_id}/update", web::post().to(update_user))
+        .route("/users/{user_id}/status", web::post().to(set_user_status))
+        .route(
+            "/users/{user_id}/reset-password",
+            web::post().to(force_password_reset),
+        )
+        .route("/users/{user_id}/delete", web::post().to(delete_user))
+        .route(
+            "/users/{user_id}/app-metadata",
+            web::post().to(update_app_metadata),
+        )
+        .route("/signup", web::post().to(signup_user))
+        .route("/login", web::post().to(authenticate_user))
+        .route("/refresh", web::post().to(refresh_token))
+        .route("/logout", web::post().to(logout_user))
+        .route("/sessions", web::get().to(list_sessions))
+        .route(
+            "/sessions/revoke-others",
+            web::post().to(revoke_other_sessions),
+        )
+        .route(
+            "/sessions/{session_id}/revoke",
+            web::post().to(revoke_session),
+        )
+        .route("/profile", web::get().to(get_profile))
+        .route("/profile/metadata", web::post().to(update_user_metadata))
+        .route("/email/verify/send", web::post().to(send_verification))
+        .route("/email/verify", web::post().to(verify_email))
+        .route("/password/reset/request", web::post().to(request_reset))
+        .route("/password/reset", web::post().to(reset_password))
+        .route("/password/change", web::post().to(change_password))
+        .route("/anonymous", web::post().to(sign_in_anonymously))
+        .route("/upgrade", web::post().to(upgrade_user))
+        .route("/magic-link/request", web::post().to(request_magic_link))
+        .route("/magic-link", web::post().to(sign_in_with_magic_link))
+        .route("/oauth/callback", web::post().to(finish_oidc))
+        .route("/oauth/{provider}/start", web::post().to(start_oidc))
+        .route("/oauth/{provider}/link", web::post().to(link_oidc))
 }
 
 async fn get_users(
     service: web::Data<ProjectAuthService>,
     info: web::Path<ProjectInfo>,
     query: Json<ProjectAuthUserListQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
+    if !caller.has_role(ProjectRole::Viewer) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
     let result = service
         .get_users(
             &info.project_id,
@@ -47,3 +131,876 @@ async fn get_users(
         }
     }
 }
+
+async fn unlock_user(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<UnlockRequest>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service.unlock(&info.project_id, &query.email).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn search_users(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<UserSearchQuery>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Viewer) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service
+        .search_users(
+            &info.project_id,
+            query.query.as_deref(),
+            query.page,
+            query.per_page.unwrap_or(DEFAULT_PAGE_SIZE),
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn create_user(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectInfo>,
+    user: Json<User>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service
+        .create_user(&info.project_id, user.into_inner())
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_user(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectUserInfo>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Viewer) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service.get_user(&info.project_id, &info.user_id).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn update_user(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectUserInfo>,
+    query: Json<AdminUpdateUser>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service
+        .admin_update_user(&info.project_id, &info.user_id, query.into_inner())
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn set_user_status(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectUserInfo>,
+    query: Json<AccountStatusRequest>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = match query.to_status() {
+        Ok(status) => {
+            service
+                .set_user_status(&info.project_id, &info.user_id, &status)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn force_password_reset(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    mailer: web::Data<dyn Mailer>,
+    info: web::Path<ProjectUserInfo>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) => {
+            service
+                .force_password_reset(
+                    &info.project_id,
+                    &info.user_id,
+                    mailer.as_ref(),
+                    project.get_mail_links().reset_password.as_deref(),
+                )
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn delete_user(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectUserInfo>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service.delete_user(&info.project_id, &info.user_id).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn signup_user(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    mailer: web::Data<dyn Mailer>,
+    info: web::Path<ProjectInfo>,
+    user: Json<User>,
+) -> impl Responder {
+    let project = match project_service.get(&info.project_id).await {
+        Ok(project) => project,
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => return HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let result = service
+        .create_user(&info.project_id, user.into_inner())
+        .await;
+    match result {
+        Ok(result) => {
+            let verification = service
+                .request_email_verification(
+                    &info.project_id,
+                    &result._id,
+                    mailer.as_ref(),
+                    project.get_mail_links().verify_email.as_deref(),
+                )
+                .await;
+            if let Err(error) = verification {
+                println!("{}", error);
+            }
+            HttpResponse::Ok().json(result)
+        }
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn authenticate_user(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    user: Json<AuthenticateUser>,
+    client: ClientInfo,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(_) => {
+            service
+                .authenticate(&info.project_id, &user.email, &user.password, &client)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn get_profile(
+    service: web::Data<ProjectAuthService>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .get_user(&authorized_user.project_id, &authorized_user.sub)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn refresh_token(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<RefreshTokenRequest>,
+) -> impl Responder {
+    let result = service
+        .refresh(&info.project_id, &query.refresh_token)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn logout_user(
+    service: web::Data<ProjectAuthService>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .logout(&authorized_user.project_id, &authorized_user.sid)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn list_sessions(
+    service: web::Data<ProjectAuthService>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .list_sessions(
+            &authorized_user.project_id,
+            &authorized_user.sub,
+            &authorized_user.sid,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn revoke_session(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectSessionInfo>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .revoke_session(&info.project_id, &authorized_user.sub, &info.session_id)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn revoke_other_sessions(
+    service: web::Data<ProjectAuthService>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .revoke_other_sessions(
+            &authorized_user.project_id,
+            &authorized_user.sub,
+            &authorized_user.sid,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn send_verification(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    mailer: web::Data<dyn Mailer>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = match project_service.get(&authorized_user.project_id).await {
+        Ok(project) => {
+            service
+                .request_email_verification(
+                    &authorized_user.project_id,
+                    &authorized_user.sub,
+                    mailer.as_ref(),
+                    project.get_mail_links().verify_email.as_deref(),
+                )
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn verify_email(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<VerifyEmailRequest>,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(_) => service.verify_email(&info.project_id, &query.token).await,
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn request_reset(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    mailer: web::Data<dyn Mailer>,
+    info: web::Path<ProjectInfo>,
+    query: Json<EmailRequest>,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) => {
+            service
+                .request_password_reset(
+                    &info.project_id,
+                    &query.email,
+                    mailer.as_ref(),
+                    project.get_mail_links().reset_password.as_deref(),
+                )
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn reset_password(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<ResetPasswordRequest>,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(_) => {
+            service
+                .reset_password(&info.project_id, &query.token, &query.password)
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn change_password(
+    service: web::Data<ProjectAuthService>,
+    query: Json<ChangePasswordRequest>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .change_password(
+            &authorized_user.project_id,
+            &authorized_user.sub,
+            &authorized_user.sid,
+            &query.current_password,
+            &query.new_password,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn start_oidc(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectProviderInfo>,
+    query: Json<OidcStartRequest>,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) => match project.get_auth_provider(&info.provider) {
+            Some(provider) => {
+                service
+                    .start_oidc(&info.project_id, provider, &query.redirect_uri, None)
+                    .await
+            }
+            None => Err(SBError::ServiceError {
+                service: String::from("project_auth"),
+                message: String::from("Unknown provider."),
+            }),
+        },
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn link_oidc(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectProviderInfo>,
+    query: Json<OidcStartRequest>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) => match project.get_auth_provider(&info.provider) {
+            Some(provider) => {
+                service
+                    .start_oidc(
+                        &info.project_id,
+                        provider,
+                        &query.redirect_uri,
+                        Some(&authorized_user.sub),
+                    )
+                    .await
+            }
+            None => Err(SBError::ServiceError {
+                service: String::from("project_auth"),
+                message: String::from("Unknown provider."),
+            }),
+        },
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn finish_oidc(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<OidcCallbackRequest>,
+    client: ClientInfo,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) => {
+            service
+                .finish_oidc(
+                    &info.project_id,
+                    &project.auth_providers,
+                    &query.state,
+                    &query.code,
+                    &client,
+                )
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn sign_in_anonymously(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    info: web::Path<ProjectInfo>,
+    client: ClientInfo,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) if project.allows_anonymous_sign_in() => {
+            service.sign_in_anonymously(&info.project_id, &client).await
+        }
+        Ok(_) => Err(SBError::ServiceError {
+            service: String::from("project_auth"),
+            message: String::from("Anonymous sign in is disabled."),
+        }),
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn upgrade_user(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    mailer: web::Data<dyn Mailer>,
+    query: Json<UpgradeUser>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let project = match project_service.get(&authorized_user.project_id).await {
+        Ok(project) => project,
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => return HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let result = service
+        .upgrade_anonymous(
+            &authorized_user.project_id,
+            &authorized_user.sub,
+            &query.email,
+            &query.password,
+            query.username.as_deref(),
+        )
+        .await;
+    match result {
+        Ok(result) => {
+            let verification = service
+                .request_email_verification(
+                    &authorized_user.project_id,
+                    &authorized_user.sub,
+                    mailer.as_ref(),
+                    project.get_mail_links().verify_email.as_deref(),
+                )
+                .await;
+            if let Err(error) = verification {
+                println!("{}", error);
+            }
+            HttpResponse::Ok().json(result)
+        }
+        Err(SBError::ValidationError { errors, service: _ }) => {
+            HttpResponse::build(http::StatusCode::BAD_REQUEST).json(doc! {"errors": errors})
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn request_magic_link(
+    service: web::Data<ProjectAuthService>,
+    project_service: web::Data<ProjectService>,
+    mailer: web::Data<dyn Mailer>,
+    info: web::Path<ProjectInfo>,
+    query: Json<EmailRequest>,
+) -> impl Responder {
+    let result = match project_service.get(&info.project_id).await {
+        Ok(project) => {
+            service
+                .request_magic_link(
+                    &info.project_id,
+                    &query.email,
+                    mailer.as_ref(),
+                    project.get_mail_links().magic_link.as_deref(),
+                )
+                .await
+        }
+        Err(error) => Err(error),
+    };
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn sign_in_with_magic_link(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<MagicLinkRequest>,
+    client: ClientInfo,
+) -> impl Responder {
+    let result = service
+        .sign_in_with_magic_link(&info.project_id, &query.token, &client)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn update_user_metadata(
+    service: web::Data<ProjectAuthService>,
+    query: Json<Document>,
+    authorized_user: ProjectEndUser,
+) -> impl Responder {
+    let result = service
+        .update_user_metadata(&authorized_user.project_id, &authorized_user.sub, &query)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn update_app_metadata(
+    service: web::Data<ProjectAuthService>,
+    info: web::Path<ProjectUserInfo>,
+    query: Json<Document>,
+    caller: RuleCaller,
+) -> impl Responder {
+    if !caller.has_role(ProjectRole::Admin) {
+        return HttpResponse::Unauthorized().finish();
+    }
+
+    let result = service
+        .update_app_metadata(&info.project_id, &info.user_id, &query)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
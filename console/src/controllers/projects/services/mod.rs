@@ -2,6 +2,7 @@ use actix_web::{web, Scope};
 
 mod auth_service;
 mod mongodb_service;
+mod realtime_service;
 
 pub fn get_service() -> Scope {
     let resource = web::scope("/services/{project_id}");
@@ -9,4 +10,5 @@ pub fn get_service() -> Scope {
     resource
         .service(mongodb_service::get_service())
         .service(auth_service::get_service())
+        .service(realtime_service::get_service())
 }
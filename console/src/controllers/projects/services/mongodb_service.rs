@@ -1,12 +1,16 @@
-use crate::models::project::ProjectUser;
+use crate::models::bulk::{BulkOperation, TransactionOperation};
+use crate::models::project::{AdminAccess, ProjectUser};
+use crate::models::rules::{ProjectRules, RuleCaller};
 use crate::services::project_mongodb::ProjectMongoDBService;
+use actix_web::error::ErrorInternalServerError;
 use actix_web::{http, web, HttpResponse, Responder, Scope};
 use error::SBError;
+use futures::StreamExt;
 use mongodb::{
     bson::Document,
     options::{
-        CreateCollectionOptions, DeleteOptions, DropCollectionOptions, FindOneOptions, FindOptions,
-        InsertOneOptions, ReplaceOptions, UpdateOptions,
+        AggregateOptions, CreateCollectionOptions, DeleteOptions, DropCollectionOptions,
+        FindOneOptions, FindOptions, InsertOneOptions, ReplaceOptions, UpdateOptions,
     },
 };
 use serde::Deserialize;
@@ -44,6 +48,14 @@ struct ProjectDropCollectionQuery {
 struct ProjectDocumentQuery {
     pub filter: Option<Document>,
     pub options: Option<FindOptions>,
+    /// `nextCursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProjectAggregateQuery {
+    pub pipeline: Vec<Document>,
+    pub options: Option<AggregateOptions>,
 }
 
 #[derive(Deserialize)]
@@ -52,6 +64,29 @@ struct ProjectCreateDocumentQuery {
     pub options: Option<InsertOneOptions>,
 }
 
+fn default_ordered() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct ProjectCreateDocumentsQuery {
+    pub documents: Vec<Document>,
+    #[serde(default = "default_ordered")]
+    pub ordered: bool,
+}
+
+#[derive(Deserialize)]
+struct ProjectBulkWriteQuery {
+    pub operations: Vec<BulkOperation>,
+    #[serde(default = "default_ordered")]
+    pub ordered: bool,
+}
+
+#[derive(Deserialize)]
+struct ProjectTransactionQuery {
+    pub operations: Vec<TransactionOperation>,
+}
+
 #[derive(Deserialize)]
 struct ProjectGetByIdDocumentQuery {
     pub options: Option<FindOneOptions>,
@@ -91,7 +126,10 @@ pub fn get_service() -> Scope {
     let resource = web::scope("/mongodb");
 
     resource
+        .route("/rules", web::get().to(get_rules))
+        .route("/rules/set", web::post().to(set_rules))
         .route("/collections", web::get().to(get_collection))
+        .route("/transaction", web::post().to(run_transaction))
         .route(
             "/collections/{collection_name}/create",
             web::post().to(create_collection),
@@ -100,10 +138,18 @@ pub fn get_service() -> Scope {
             "/collections/{collection_name}/drop",
             web::post().to(drop_collection),
         )
+        .route(
+            "/collections/{collection_name}/aggregate",
+            web::post().to(aggregate),
+        )
         .route(
             "/collections/{collection_name}/documents",
             web::post().to(get_documents),
         )
+        .route(
+            "/collections/{collection_name}/documents/stream",
+            web::post().to(stream_documents),
+        )
         .route(
             "/collections/{collection_name}/documents/delete",
             web::post().to(delete_documents),
@@ -132,14 +178,65 @@ pub fn get_service() -> Scope {
             "/collections/{collection_name}/documents/create",
             web::post().to(create_document),
         )
+        .route(
+            "/collections/{collection_name}/documents/create-many",
+            web::post().to(create_documents),
+        )
+        .route(
+            "/collections/{collection_name}/bulk",
+            web::post().to(bulk_write),
+        )
+}
+
+async fn get_rules(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectInfo>,
+    _authorized_user: ProjectUser,
+) -> impl Responder {
+    let result = service.get_rules(&info.project_id).await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn set_rules(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectInfo>,
+    rules: Json<ProjectRules>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service
+        .set_rules(&info.project_id, rules.into_inner())
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
 }
 
 async fn get_collection(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectInfo>,
-    _authorized_user: Option<ProjectUser>,
+    caller: RuleCaller,
 ) -> impl Responder {
-    let result = service.get_collections_for_project(&info.project_id).await;
+    let result = service
+        .get_collections_for_project(&info.project_id, &caller)
+        .await;
     match result {
         Ok(result) => HttpResponse::Ok().json(result),
         Err(SBError::ServiceError {
@@ -157,12 +254,13 @@ async fn create_collection(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectCollectionInfo>,
     options: Json<ProjectCreateCollectionQuery>,
-    _authorized_user: Option<ProjectUser>,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .create_collection(
             &info.project_id,
             &info.collection_name,
+            &caller,
             options.options.clone(),
         )
         .await;
@@ -183,12 +281,13 @@ async fn drop_collection(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectCollectionInfo>,
     options: Json<ProjectDropCollectionQuery>,
-    _authorized_user: Option<ProjectUser>,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .drop_collection(
             &info.project_id,
             &info.collection_name,
+            &caller,
             options.options.clone(),
         )
         .await;
@@ -209,14 +308,16 @@ async fn get_documents(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectCollectionInfo>,
     query: Json<ProjectDocumentQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .get_documents_from_collection(
             &info.project_id,
             &info.collection_name,
+            &caller,
             query.filter.clone(),
             query.options.clone(),
+            query.cursor.as_deref(),
         )
         .await;
     match result {
@@ -232,16 +333,89 @@ async fn get_documents(
     }
 }
 
+async fn aggregate(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectCollectionInfo>,
+    query: Json<ProjectAggregateQuery>,
+    caller: RuleCaller,
+) -> impl Responder {
+    let query = query.into_inner();
+    let result = service
+        .aggregate(
+            &info.project_id,
+            &info.collection_name,
+            &caller,
+            query.pipeline,
+            query.options,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// One line of an NDJSON response.
+fn ndjson_line(document: mongodb::error::Result<Document>) -> Result<web::Bytes, actix_web::Error> {
+    let document = document.map_err(|error| {
+        println!("{}", error);
+        ErrorInternalServerError("Failure querying documents.")
+    })?;
+    let mut line = serde_json::to_vec(&document).map_err(ErrorInternalServerError)?;
+    line.push(b'\n');
+    Ok(web::Bytes::from(line))
+}
+
+/// Writes the documents as NDJSON while the MongoDB cursor yields them.
+async fn stream_documents(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectCollectionInfo>,
+    query: Json<ProjectDocumentQuery>,
+    caller: RuleCaller,
+) -> impl Responder {
+    let result = service
+        .stream_documents_from_collection(
+            &info.project_id,
+            &info.collection_name,
+            &caller,
+            query.filter.clone(),
+            query.options.clone(),
+            query.cursor.as_deref(),
+        )
+        .await;
+    match result {
+        Ok(cursor) => HttpResponse::Ok()
+            .content_type("application/x-ndjson")
+            .streaming(cursor.map(ndjson_line)),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 async fn get_document_by_id(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectDocumentInfo>,
     options: Json<ProjectGetByIdDocumentQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .get_document_by_id_from_collection(
             &info.project_id,
             &info.collection_name,
+            &caller,
             &info.document_id,
             options.options.clone(),
         )
@@ -263,12 +437,13 @@ async fn create_document(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectCollectionInfo>,
     query: Json<ProjectCreateDocumentQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .create_document(
             &info.project_id,
             &info.collection_name,
+            &caller,
             query.document.clone(),
             query.options.clone(),
         )
@@ -286,16 +461,97 @@ async fn create_document(
     }
 }
 
+async fn create_documents(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectCollectionInfo>,
+    query: Json<ProjectCreateDocumentsQuery>,
+    caller: RuleCaller,
+) -> impl Responder {
+    let query = query.into_inner();
+    let result = service
+        .insert_documents(
+            &info.project_id,
+            &info.collection_name,
+            &caller,
+            query.documents,
+            query.ordered,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn bulk_write(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectCollectionInfo>,
+    query: Json<ProjectBulkWriteQuery>,
+    caller: RuleCaller,
+) -> impl Responder {
+    let query = query.into_inner();
+    let result = service
+        .bulk_write(
+            &info.project_id,
+            &info.collection_name,
+            &caller,
+            query.operations,
+            query.ordered,
+        )
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn run_transaction(
+    service: web::Data<ProjectMongoDBService>,
+    info: web::Path<ProjectInfo>,
+    query: Json<ProjectTransactionQuery>,
+    caller: RuleCaller,
+) -> impl Responder {
+    let result = service
+        .run_transaction(&info.project_id, &caller, query.into_inner().operations)
+        .await;
+    match result {
+        Ok(result) => HttpResponse::Ok().json(result),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
 async fn delete_documents(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectCollectionInfo>,
     query: Json<ProjectDeleteDocumentQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .delete_documents(
             &info.project_id,
             &info.collection_name,
+            &caller,
             query.filter.clone(),
             query.options.clone(),
         )
@@ -317,12 +573,13 @@ async fn delete_document_by_id(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectDocumentInfo>,
     query: Json<ProjectDeleteDocumentByIdQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .delete_document(
             &info.project_id,
             &info.collection_name,
+            &caller,
             &info.document_id,
             query.options.clone(),
         )
@@ -344,12 +601,13 @@ async fn update_documents(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectCollectionInfo>,
     query: Json<ProjectUpdateDocumentQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .update_documents(
             &info.project_id,
             &info.collection_name,
+            &caller,
             query.filter.clone(),
             query.update.clone(),
             query.options.clone(),
@@ -372,12 +630,13 @@ async fn update_document_by_id(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectDocumentInfo>,
     query: Json<ProjectUpdateDocumentByIdQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .update_document(
             &info.project_id,
             &info.collection_name,
+            &caller,
             &info.document_id,
             query.update.clone(),
             query.options.clone(),
@@ -400,12 +659,13 @@ async fn set_document(
     service: web::Data<ProjectMongoDBService>,
     info: web::Path<ProjectDocumentInfo>,
     query: Json<ProjectReplaceDocumentByIdQuery>,
-    _authorized_user: ProjectUser,
+    caller: RuleCaller,
 ) -> impl Responder {
     let result = service
         .set_document(
             &info.project_id,
             &info.collection_name,
+            &caller,
             &info.document_id,
             query.set.clone(),
             query.options.clone(),
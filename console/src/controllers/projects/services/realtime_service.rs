@@ -0,0 +1,418 @@
+use crate::models::changes::ChangeEvent;
+use crate::models::project::{authenticate_caller, Credentials};
+use crate::models::rules::{RuleCaller, RuleDecision, RuleOperation};
+use crate::services::project_mongodb::ProjectMongoDBService;
+use crate::services::tickets::{TicketStore, TICKET_TTL};
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::{http, rt, web, HttpRequest, HttpResponse, Responder, Scope};
+use error::{SBError, SBResult};
+use futures::channel::mpsc;
+use futures::future::ready;
+use futures::stream::LocalBoxStream;
+use futures::{stream, Stream, StreamExt};
+use mongodb::bson::Document;
+use mongodb::Cursor;
+use serde::{Deserialize, Serialize};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Time between keep alive messages on idle subscriptions.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Deserialize)]
+struct ProjectInfo {
+    pub project_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectCollectionInfo {
+    pub project_id: String,
+    pub collection_name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubscribeQuery {
+    /// Document filter, as JSON.
+    pub filter: Option<String>,
+    /// `resumeToken` of the last event received.
+    pub resume_after: Option<String>,
+    /// Ticket standing for the credentials, for clients that cannot set
+    /// headers.
+    pub ticket: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Ticket {
+    pub ticket: String,
+    /// Seconds the ticket can be redeemed in.
+    pub expires_in: u64,
+}
+
+pub fn get_service() -> Scope {
+    let resource = web::scope("/realtime");
+
+    resource
+        .route("/ticket", web::post().to(create_ticket))
+        .route(
+            "/collections/{collection_name}/ws",
+            web::get().to(subscribe_websocket),
+        )
+        .route(
+            "/collections/{collection_name}/sse",
+            web::get().to(subscribe_events),
+        )
+}
+
+/// Message pushed on a subscription.
+enum Push {
+    Change(Box<ChangeEvent>),
+    Heartbeat,
+    /// Frame answering the client, WebSocket only.
+    Reply(ws::Message),
+    End,
+    Failed,
+    /// The caller lost access to the collection or their token expired.
+    Unauthorized,
+}
+
+impl Push {
+    fn is_closing(&self) -> bool {
+        matches!(
+            self,
+            Push::End | Push::Failed | Push::Unauthorized | Push::Reply(ws::Message::Close(_))
+        )
+    }
+}
+
+/// Caller of an open subscription, checked again on every heartbeat.
+struct Subscription {
+    req: HttpRequest,
+    service: web::Data<ProjectMongoDBService>,
+    project_id: String,
+    collection_name: String,
+    credentials: Credentials,
+    caller: RuleCaller,
+    decision: RuleDecision,
+}
+
+impl Subscription {
+    /// Whether the credentials still authenticate the same caller and the
+    /// rules still give them the same read access, so sessions revoked,
+    /// accounts disabled and rules changed end the subscription.
+    async fn is_authorized(&self) -> bool {
+        match authenticate_caller(&self.req, &self.project_id, &self.credentials).await {
+            Ok((caller, _)) if caller == self.caller => matches!(
+                self.service
+                    .authorize(
+                        &self.project_id,
+                        &self.collection_name,
+                        RuleOperation::Read,
+                        &self.caller,
+                    )
+                    .await,
+                Ok(decision) if decision == self.decision
+            ),
+            _ => false,
+        }
+    }
+}
+
+/// Credentials of a subscription, those of the ticket when one is given.
+fn subscription_credentials(
+    req: &HttpRequest,
+    tickets: &TicketStore,
+    project_id: &str,
+    query: &SubscribeQuery,
+) -> Result<Credentials, actix_web::Error> {
+    match &query.ticket {
+        Some(ticket) => tickets
+            .redeem(project_id, ticket)
+            .ok_or_else(|| actix_web::error::ErrorUnauthorized("Invalid ticket")),
+        None => Credentials::from_request(req),
+    }
+}
+
+/// Heartbeats while the caller is still authorized, then a closing
+/// `Unauthorized`, which also comes when the token of the caller expires.
+fn checks(subscription: Subscription, exp: Option<usize>) -> LocalBoxStream<'static, Push> {
+    let heartbeats = stream::unfold(
+        (
+            rt::time::interval_at(
+                rt::time::Instant::now() + HEARTBEAT_INTERVAL,
+                HEARTBEAT_INTERVAL,
+            ),
+            Rc::new(subscription),
+        ),
+        |(mut interval, subscription)| async move {
+            interval.tick().await;
+            let push = match subscription.is_authorized().await {
+                true => Push::Heartbeat,
+                false => Push::Unauthorized,
+            };
+            Some((push, (interval, subscription)))
+        },
+    );
+    let expiry = match exp {
+        Some(exp) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or_default();
+            let remaining = Duration::from_secs((exp as u64).saturating_sub(now));
+            stream::once(async move {
+                rt::time::sleep(remaining).await;
+                Push::Unauthorized
+            })
+            .boxed_local()
+        }
+        None => stream::pending().boxed_local(),
+    };
+    stream::select(heartbeats, expiry).boxed_local()
+}
+
+/// Issues a ticket for the credentials of the request, to open a
+/// subscription with `?ticket=` where headers cannot be set.
+async fn create_ticket(
+    req: HttpRequest,
+    tickets: web::Data<TicketStore>,
+    info: web::Path<ProjectInfo>,
+) -> impl Responder {
+    let credentials = match Credentials::from_request(&req) {
+        Ok(credentials) => credentials,
+        Err(error) => return HttpResponse::from_error(error),
+    };
+    match authenticate_caller(&req, &info.project_id, &credentials).await {
+        Ok(_) => HttpResponse::Ok().json(Ticket {
+            ticket: tickets.issue(&info.project_id, credentials),
+            expires_in: TICKET_TTL.as_secs(),
+        }),
+        Err(error) => HttpResponse::from_error(error),
+    }
+}
+
+/// Authenticates the subscriber and opens the change stream, along with the
+/// checks ending it once the subscriber loses access.
+async fn watch(
+    req: &HttpRequest,
+    service: web::Data<ProjectMongoDBService>,
+    tickets: &TicketStore,
+    info: &ProjectCollectionInfo,
+    query: &SubscribeQuery,
+    resume_after: Option<&str>,
+) -> Result<(Cursor<Document>, LocalBoxStream<'static, Push>), HttpResponse> {
+    let (credentials, caller, exp) =
+        match subscription_credentials(req, tickets, &info.project_id, query) {
+            Ok(credentials) => match authenticate_caller(req, &info.project_id, &credentials).await
+            {
+                Ok((caller, exp)) => (credentials, caller, exp),
+                Err(error) => return Err(HttpResponse::from_error(error)),
+            },
+            Err(error) => return Err(HttpResponse::from_error(error)),
+        };
+    match open_stream(&service, info, &caller, query, resume_after).await {
+        Ok((decision, cursor)) => {
+            let subscription = Subscription {
+                req: req.clone(),
+                service,
+                project_id: info.project_id.clone(),
+                collection_name: info.collection_name.clone(),
+                credentials,
+                caller,
+                decision,
+            };
+            Ok((cursor, checks(subscription, exp)))
+        }
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => Err(HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message)),
+        Err(error) => {
+            println!("{}", error);
+            Err(HttpResponse::InternalServerError().finish())
+        }
+    }
+}
+
+async fn open_stream(
+    service: &ProjectMongoDBService,
+    info: &ProjectCollectionInfo,
+    caller: &RuleCaller,
+    query: &SubscribeQuery,
+    resume_after: Option<&str>,
+) -> SBResult<(RuleDecision, Cursor<Document>)> {
+    let filter =
+        match &query.filter {
+            Some(filter) => Some(serde_json::from_str::<Document>(filter).map_err(|_| {
+                SBError::ServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Invalid filter."),
+                }
+            })?),
+            None => None,
+        };
+    service
+        .watch_collection(
+            &info.project_id,
+            &info.collection_name,
+            caller,
+            filter,
+            resume_after.or(query.resume_after.as_deref()),
+        )
+        .await
+}
+
+/// Change events of the cursor mixed with the checks, ending after the
+/// first closing push.
+fn pushes(
+    cursor: Cursor<Document>,
+    checks: LocalBoxStream<'static, Push>,
+    replies: impl Stream<Item = Push> + 'static,
+) -> LocalBoxStream<'static, Push> {
+    let changes = cursor
+        .filter_map(|change| {
+            ready(match change {
+                Ok(change) => {
+                    ChangeEvent::from_change(&change).map(|event| Push::Change(Box::new(event)))
+                }
+                Err(error) => {
+                    println!("{}", error);
+                    Some(Push::Failed)
+                }
+            })
+        })
+        .chain(stream::once(ready(Push::End)));
+    stream::select(stream::select(changes, checks), replies)
+        .scan(false, |closed, push| {
+            if *closed {
+                return ready(None);
+            }
+            *closed = push.is_closing();
+            ready(Some(push))
+        })
+        .boxed_local()
+}
+
+/// Reads the client frames, answering pings and closes.
+async fn read_frames(mut payload: web::Payload, replies: mpsc::UnboundedSender<Push>) {
+    let mut codec = ws::Codec::new();
+    let mut buffer = web::BytesMut::new();
+    while let Some(Ok(chunk)) = payload.next().await {
+        buffer.extend_from_slice(&chunk);
+        loop {
+            let reply = match codec.decode(&mut buffer) {
+                Ok(Some(ws::Frame::Ping(message))) => ws::Message::Pong(message),
+                Ok(Some(ws::Frame::Close(reason))) => ws::Message::Close(reason),
+                Ok(Some(_)) => continue,
+                Ok(None) => break,
+                Err(_) => ws::Message::Close(Some(ws::CloseCode::Protocol.into())),
+            };
+            let closing = matches!(reply, ws::Message::Close(_));
+            if replies.unbounded_send(Push::Reply(reply)).is_err() || closing {
+                return;
+            }
+        }
+    }
+    let _ = replies.unbounded_send(Push::Reply(ws::Message::Close(None)));
+}
+
+fn websocket_frame(codec: &mut ws::Codec, push: Push) -> Result<web::Bytes, actix_web::Error> {
+    let message = match push {
+        Push::Change(event) => ws::Message::Text(
+            serde_json::to_string(&event)
+                .map_err(ErrorInternalServerError)?
+                .into(),
+        ),
+        Push::Heartbeat => ws::Message::Ping(web::Bytes::new()),
+        Push::Reply(message) => message,
+        Push::End => ws::Message::Close(Some(ws::CloseCode::Normal.into())),
+        Push::Failed => ws::Message::Close(Some(ws::CloseCode::Error.into())),
+        Push::Unauthorized => ws::Message::Close(Some(ws::CloseReason {
+            code: ws::CloseCode::Policy,
+            description: Some(String::from("Unauthorized")),
+        })),
+    };
+    let mut frame = web::BytesMut::new();
+    codec
+        .encode(message, &mut frame)
+        .map_err(ErrorInternalServerError)?;
+    Ok(frame.freeze())
+}
+
+/// Pushes the changes of a collection as WebSocket text messages, one
+/// `ChangeEvent` each.
+async fn subscribe_websocket(
+    req: HttpRequest,
+    payload: web::Payload,
+    service: web::Data<ProjectMongoDBService>,
+    tickets: web::Data<TicketStore>,
+    info: web::Path<ProjectCollectionInfo>,
+    query: web::Query<SubscribeQuery>,
+) -> impl Responder {
+    let key = match ws::verify_handshake(req.head()) {
+        Ok(()) => match req.headers().get(header::SEC_WEBSOCKET_KEY) {
+            Some(key) => ws::hash_key(key.as_bytes()),
+            None => return HttpResponse::build(http::StatusCode::BAD_REQUEST).finish(),
+        },
+        Err(error) => {
+            return HttpResponse::build(http::StatusCode::BAD_REQUEST).body(error.to_string())
+        }
+    };
+    let (cursor, checks) = match watch(&req, service, &tickets, &info, &query, None).await {
+        Ok(stream) => stream,
+        Err(response) => return response,
+    };
+    let (sender, replies) = mpsc::unbounded();
+    rt::spawn(read_frames(payload, sender));
+    let mut codec = ws::Codec::new();
+    HttpResponse::build(http::StatusCode::SWITCHING_PROTOCOLS)
+        .upgrade("websocket")
+        .insert_header((header::TRANSFER_ENCODING, "chunked"))
+        .insert_header((
+            header::SEC_WEBSOCKET_ACCEPT,
+            HeaderValue::from_bytes(&key).expect("accept keys are base64"),
+        ))
+        .streaming(
+            pushes(cursor, checks, replies).map(move |push| websocket_frame(&mut codec, push)),
+        )
+}
+
+fn event_message(push: Push) -> Result<web::Bytes, actix_web::Error> {
+    let message = match push {
+        Push::Change(event) => format!(
+            "id: {}\ndata: {}\n\n",
+            event.resume_token,
+            serde_json::to_string(&event).map_err(ErrorInternalServerError)?
+        ),
+        Push::Heartbeat => String::from(": keep-alive\n\n"),
+        Push::Unauthorized => String::from("event: unauthorized\ndata: Unauthorized.\n\n"),
+        _ => String::new(),
+    };
+    Ok(web::Bytes::from(message))
+}
+
+/// Pushes the changes of a collection as Server-Sent Events. The event ids
+/// are resume tokens, so reconnecting clients resume with `Last-Event-ID`.
+async fn subscribe_events(
+    req: HttpRequest,
+    service: web::Data<ProjectMongoDBService>,
+    tickets: web::Data<TicketStore>,
+    info: web::Path<ProjectCollectionInfo>,
+    query: web::Query<SubscribeQuery>,
+) -> impl Responder {
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|id| id.to_str().ok());
+    match watch(&req, service, &tickets, &info, &query, last_event_id).await {
+        Ok((cursor, checks)) => HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .insert_header((header::CACHE_CONTROL, "no-cache"))
+            .streaming(pushes(cursor, checks, stream::empty()).map(event_message)),
+        Err(response) => response,
+    }
+}
@@ -1,14 +1,33 @@
-use crate::models::project::Project;
+use crate::models::project::{
+    AdminAccess, OwnerAccess, Project, ProjectMember, ProjectRole, ProjectUpdate, ProjectUser,
+};
+use crate::services::project_mongodb::ProjectMongoDBService;
 use crate::services::projects::ProjectService;
 use actix_web::{http, web, HttpResponse, Responder, Scope};
 use auth::models::users::AuthorizedUser;
 use error::SBError;
+use serde::Deserialize;
 use web::Json;
 
+#[derive(Deserialize)]
+struct Info {
+    pub project_id: String,
+}
+
+#[derive(Deserialize)]
+struct ProjectDeleteQuery {
+    #[serde(default)]
+    pub soft: bool,
+}
+
 pub fn get_service() -> Scope {
     let resource = web::scope("/edit");
 
-    resource.route("/new", web::post().to(create_project))
+    resource
+        .route("/new", web::post().to(create_project))
+        .route("/{project_id}/update", web::post().to(update_project))
+        .route("/{project_id}/delete", web::post().to(delete_project))
+        .route("/{project_id}/restore", web::post().to(restore_project))
 }
 
 async fn create_project(
@@ -19,7 +38,16 @@ async fn create_project(
     let project = Project {
         id: Option::None,
         name: (*project_payload.name).to_owned(),
-        users: vec![authorized_user.sub],
+        description: project_payload.description.clone(),
+        settings: project_payload.settings.clone(),
+        users: vec![ProjectMember {
+            user_id: authorized_user.sub,
+            role: ProjectRole::Owner,
+        }],
+        invitations: vec![],
+        deleted_at: None,
+        api_keys: vec![],
+        auth_providers: vec![],
     };
 
     let result = service.create(project).await;
@@ -35,3 +63,79 @@ async fn create_project(
         }
     }
 }
+
+async fn update_project(
+    service: web::Data<ProjectService>,
+    info: web::Path<Info>,
+    updates: Json<ProjectUpdate>,
+    _authorized_user: ProjectUser<AdminAccess>,
+) -> impl Responder {
+    let result = service.update(&info.project_id, updates.into_inner()).await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn delete_project(
+    service: web::Data<ProjectService>,
+    mongodb_service: web::Data<ProjectMongoDBService>,
+    info: web::Path<Info>,
+    query: Json<ProjectDeleteQuery>,
+    _authorized_user: ProjectUser<OwnerAccess>,
+) -> impl Responder {
+    let result = match query.soft {
+        true => service.soft_delete(&info.project_id).await,
+        // The document is only removed once the database is dropped, so a
+        // failed drop leaves a deleted project for the purge to retry.
+        false => match service.mark_deleted(&info.project_id).await {
+            Ok(_) => match mongodb_service
+                .drop_project_database(&info.project_id)
+                .await
+            {
+                Ok(_) => service.delete(&info.project_id).await,
+                Err(error) => Err(error),
+            },
+            Err(error) => Err(error),
+        },
+    };
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+async fn restore_project(
+    service: web::Data<ProjectService>,
+    info: web::Path<Info>,
+    authorized_user: AuthorizedUser,
+) -> impl Responder {
+    let result = service
+        .restore(&info.project_id, &authorized_user.sub)
+        .await;
+    match result {
+        Ok(_) => HttpResponse::Ok().json(true),
+        Err(SBError::ServiceError {
+            message,
+            service: _,
+        }) => HttpResponse::build(http::StatusCode::BAD_REQUEST).body(message),
+        Err(error) => {
+            println!("{}", error);
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
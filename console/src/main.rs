@@ -1,15 +1,24 @@
 use actix_cors::Cors;
 use actix_web::{get, middleware, web, App, HttpResponse, HttpServer, Responder};
+use auth::mailer::{FileMailer, Mailer, SmtpMailer};
+use auth::models::sessions::TrustedProxy;
+use auth::services::keys::KeyStore;
+use auth::services::password_hashing::{self, PasswordHashing};
+use auth::services::password_policy::PasswordPolicy;
+use auth::services::status_cache::StatusCache;
 use auth::services::AuthenticationService;
-use dotenv;
 use error::SBError;
+use services::project_auth::ProjectKeys;
 use std::env;
 use std::result::Result;
+use std::sync::Arc;
+use std::time::Duration;
 
 mod controllers;
 mod database;
 mod models;
 mod services;
+mod tasks;
 
 #[get("/hello")]
 async fn hello() -> impl Responder {
@@ -17,7 +26,7 @@ async fn hello() -> impl Responder {
 }
 
 fn get_var(name: &str) -> String {
-    env::var(name).expect(format!("Expected environment variable {} to be set", name).as_ref())
+    env::var(name).unwrap_or_else(|_| panic!("Expected environment variable {} to be set", name))
 }
 
 async fn build_db_client_data() -> Result<mongodb::Client, SBError> {
@@ -32,11 +41,115 @@ fn build_db_data(client: mongodb::Client) -> mongodb::Database {
     client.database(&db_name)
 }
 
-fn build_auth_data(db: mongodb::Database) -> AuthenticationService {
+/// Loads the asymmetric keys listed in `keys_name`, falling back to a HS256
+/// secret when none are configured.
+fn build_key_store(keys_name: &str, secret_name: &str) -> KeyStore {
+    match env::var(keys_name) {
+        Ok(config) => KeyStore::from_config(&config)
+            .unwrap_or_else(|error| panic!("Invalid {}: {}", keys_name, error)),
+        Err(_) => KeyStore::from_secret(&get_var(secret_name)),
+    }
+}
+
+/// `iss` claim of the console tokens, also prefixing the issuers of the
+/// projects.
+fn get_token_issuer() -> String {
+    env::var("TOKEN_ISSUER").unwrap_or_else(|_| String::from("snellbaas"))
+}
+
+/// Project keys from `PROJECT_SIGNING_KEYS`, and the keys of single projects
+/// from `PROJECT_SIGNING_KEYS_{project_id}`. Projects without keys of their
+/// own fall back to the shared ones.
+fn build_project_keys(issuer: &str) -> ProjectKeys {
+    let projects = env::vars()
+        .filter_map(|(name, config)| {
+            let project_id = name.strip_prefix("PROJECT_SIGNING_KEYS_")?.to_lowercase();
+            let keys = KeyStore::from_config(&config)
+                .unwrap_or_else(|error| panic!("Invalid {}: {}", name, error));
+            Some((project_id, keys))
+        })
+        .collect();
+    ProjectKeys {
+        default: build_key_store("PROJECT_SIGNING_KEYS", "PROJECT_AUTH_SECRET"),
+        projects,
+        issuer: String::from(issuer),
+    }
+}
+
+fn build_auth_data(
+    db: mongodb::Database,
+    keys: KeyStore,
+    password_policy: PasswordPolicy,
+    password_hashing: PasswordHashing,
+) -> AuthenticationService {
     let user_collection_name = get_var("MONGO_USER_COLLECTION");
-    let secret = get_var("SECRET");
 
-    AuthenticationService::init(db, user_collection_name, secret)
+    AuthenticationService::init(
+        db,
+        user_collection_name,
+        keys,
+        password_policy,
+        password_hashing,
+    )
+}
+
+fn get_flag(name: &str) -> bool {
+    env::var(name).map(|value| value == "true").unwrap_or(false)
+}
+
+fn get_number(name: &str, default: u32) -> u32 {
+    env::var(name)
+        .map(|value| {
+            value
+                .parse::<u32>()
+                .unwrap_or_else(|_| panic!("Expected {} to be a number", name))
+        })
+        .unwrap_or(default)
+}
+
+/// `PASSWORD_HASHER` selects `bcrypt` (default) or `argon2id`, existing
+/// hashes are upgraded when their users log in.
+fn build_password_hashing() -> PasswordHashing {
+    match env::var("PASSWORD_HASHER").as_deref() {
+        Ok("argon2id") => PasswordHashing::Argon2id {
+            memory_cost: get_number(
+                "ARGON2_MEMORY_COST",
+                password_hashing::DEFAULT_ARGON2_MEMORY_COST,
+            ),
+            time_cost: get_number(
+                "ARGON2_TIME_COST",
+                password_hashing::DEFAULT_ARGON2_TIME_COST,
+            ),
+            parallelism: get_number(
+                "ARGON2_PARALLELISM",
+                password_hashing::DEFAULT_ARGON2_PARALLELISM,
+            ),
+        },
+        Ok("bcrypt") | Err(_) => PasswordHashing::Bcrypt {
+            cost: get_number("BCRYPT_COST", password_hashing::DEFAULT_BCRYPT_COST),
+        },
+        Ok(hasher) => panic!("Unsupported PASSWORD_HASHER {}", hasher),
+    }
+}
+
+fn build_password_policy() -> PasswordPolicy {
+    let mut policy = PasswordPolicy::default();
+    if let Ok(min_length) = env::var("PASSWORD_MIN_LENGTH") {
+        policy.min_length = min_length
+            .parse::<usize>()
+            .expect("Expected PASSWORD_MIN_LENGTH to be a number");
+    }
+    policy.require_lowercase = get_flag("PASSWORD_REQUIRE_LOWERCASE");
+    policy.require_uppercase = get_flag("PASSWORD_REQUIRE_UPPERCASE");
+    policy.require_digit = get_flag("PASSWORD_REQUIRE_DIGIT");
+    policy.require_symbol = get_flag("PASSWORD_REQUIRE_SYMBOL");
+    match env::var("BREACHED_PASSWORDS_FILE") {
+        Ok(path) => policy.with_breached_passwords(
+            PasswordPolicy::load_breached_passwords(&path)
+                .unwrap_or_else(|error| panic!("Invalid BREACHED_PASSWORDS_FILE: {}", error)),
+        ),
+        Err(_) => policy,
+    }
 }
 
 fn build_project_data(db: mongodb::Database) -> services::projects::ProjectService {
@@ -44,34 +157,137 @@ fn build_project_data(db: mongodb::Database) -> services::projects::ProjectServi
     services::projects::ProjectService::new(db.collection(project_collection_name.as_ref()))
 }
 
+/// SMTP when `SMTP_HOST` is set, otherwise mails are written to `MAIL_FILE`
+/// or printed.
+fn build_mailer() -> Arc<dyn Mailer> {
+    match env::var("SMTP_HOST") {
+        Ok(host) => Arc::new(SmtpMailer {
+            host,
+            port: get_var("SMTP_PORT")
+                .parse::<u16>()
+                .expect("Expected SMTP_PORT to be a port number"),
+            tls: env::var("SMTP_TLS")
+                .map(|tls| tls != "false")
+                .unwrap_or(true),
+            credentials: env::var("SMTP_USERNAME")
+                .ok()
+                .map(|username| (username, get_var("SMTP_PASSWORD"))),
+            from: get_var("MAIL_FROM"),
+        }),
+        Err(_) => Arc::new(FileMailer::new(env::var("MAIL_FILE").ok())),
+    }
+}
+
+/// With `STRICT_ACCOUNT_STATUS`, every request checks that its user is still
+/// active, statuses are cached for `ACCOUNT_STATUS_CACHE_SECONDS`.
+fn build_status_cache() -> Option<StatusCache> {
+    match get_flag("STRICT_ACCOUNT_STATUS") {
+        true => Some(StatusCache::new(Duration::from_secs(get_number(
+            "ACCOUNT_STATUS_CACHE_SECONDS",
+            30,
+        ) as u64))),
+        false => None,
+    }
+}
+
+/// `CLIENT_IP_HEADER` names the header in which the reverse proxy passes the
+/// client address, used for the login lockout and the sessions.
+fn build_trusted_proxy() -> TrustedProxy {
+    TrustedProxy {
+        header: env::var("CLIENT_IP_HEADER").ok().map(|name| {
+            name.parse()
+                .unwrap_or_else(|_| panic!("Invalid CLIENT_IP_HEADER {}", name))
+        }),
+    }
+}
+
+/// Soft deleted projects are purged after `PROJECT_DELETION_GRACE_DAYS`,
+/// 30 by default.
+fn build_deletion_grace_period() -> Duration {
+    let days = get_number("PROJECT_DELETION_GRACE_DAYS", 30) as u64;
+    Duration::from_secs(days * 86400)
+}
+
+/// Access log in the default format, except that the request line leaves
+/// out the query string, which may carry realtime tickets.
+fn build_logger() -> middleware::Logger {
+    middleware::Logger::new(r#"%a "%{REQUEST}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#)
+        .custom_request_replace("REQUEST", |req| {
+            format!("{} {} {:?}", req.method(), req.path(), req.version())
+        })
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
     env::set_var("RUST_LOG", "actix_web=debug");
     env_logger::init();
     let db_client = build_db_client_data().await.expect("DB Client init failed");
+    actix_web::rt::spawn(tasks::purge_deleted_projects(
+        build_project_data(build_db_data(db_client.clone())),
+        services::project_mongodb::ProjectMongoDBService::new(db_client.clone()),
+        build_deletion_grace_period(),
+    ));
+    let token_issuer = get_token_issuer();
+    let console_keys = build_key_store("SIGNING_KEYS", "SECRET").with_issuer(&token_issuer);
+    let project_keys = build_project_keys(&token_issuer);
+    let password_policy = build_password_policy();
+    let password_hashing = build_password_hashing();
+    let mailer = build_mailer();
+    let console_admins =
+        models::admin::ConsoleAdmins::from_list(&env::var("CONSOLE_ADMINS").unwrap_or_default());
+    let console_status_cache = build_status_cache();
+    let project_status_cache = build_status_cache();
+    let mail_links =
+        models::mail::MailLinks::from_base_url(env::var("CONSOLE_URL").ok().as_deref());
+    let realtime_tickets = services::tickets::TicketStore::new();
+    let trusted_proxy = build_trusted_proxy();
+    let http_client = auth::http::Client::new();
     HttpServer::new(move || {
         let cors = Cors::permissive();
         let db_client_data = db_client.clone();
         let db_data = build_db_data(db_client_data.clone());
-        let authentication_service = build_auth_data(db_data.clone());
+        let authentication_service = build_auth_data(
+            db_data.clone(),
+            console_keys.clone(),
+            password_policy.clone(),
+            password_hashing,
+        );
+        let authentication_service = match &console_status_cache {
+            Some(cache) => authentication_service.with_status_cache(cache.clone()),
+            None => authentication_service,
+        };
         let project_service = build_project_data(db_data.clone());
         let project_mongodb_service =
             services::project_mongodb::ProjectMongoDBService::new(db_client.clone());
         let project_auth_service = services::project_auth::ProjectAuthService::new(
             db_client.clone(),
-            get_var("PROJECT_AUTH_SECRET"),
+            project_keys.clone(),
+            password_policy.clone(),
+            password_hashing,
+            project_service.clone(),
         );
+        let project_auth_service = match &project_status_cache {
+            Some(cache) => project_auth_service.with_status_cache(cache.clone()),
+            None => project_auth_service,
+        }
+        .with_http_client(http_client.clone());
         App::new()
             .wrap(cors)
-            .wrap(middleware::Logger::default())
+            .wrap(build_logger())
             .app_data(web::Data::new(db_client_data))
             .app_data(web::Data::new(db_data))
             .app_data(web::Data::new(authentication_service))
             .app_data(web::Data::new(project_service))
             .app_data(web::Data::new(project_mongodb_service))
             .app_data(web::Data::new(project_auth_service))
+            .app_data(web::Data::from(mailer.clone()))
+            .app_data(web::Data::new(mail_links.clone()))
+            .app_data(web::Data::new(console_admins.clone()))
+            .app_data(web::Data::new(realtime_tickets.clone()))
+            .app_data(web::Data::new(trusted_proxy.clone()))
             .service(hello)
+            .service(controllers::jwks::get_service())
             .service(controllers::get_service())
             .service(controllers::console::get_service())
     })
@@ -88,9 +304,9 @@ mod tests {
 
     #[actix_rt::test]
     async fn test_index_get() {
-        let mut app = test::init_service(App::new().service(hello)).await;
-        let req = test::TestRequest::get().to_request();
-        let resp = test::call_service(&mut app, req).await;
+        let app = test::init_service(App::new().service(hello)).await;
+        let req = test::TestRequest::get().uri("/hello").to_request();
+        let resp = test::call_service(&app, req).await;
         assert!(resp.status().is_success());
         let response_content = test::read_body(resp).await;
         assert_eq!(response_content, Bytes::from_static(b"Hello world!"));
@@ -0,0 +1,50 @@
+use actix_web::error::{ErrorForbidden, ErrorInternalServerError};
+use actix_web::{dev, web, Error, FromRequest, HttpRequest};
+use auth::models::users::AuthorizedUser;
+use futures::Future;
+use std::pin::Pin;
+
+/// Ids of the console accounts allowed to manage other accounts, from
+/// `CONSOLE_ADMINS`.
+#[derive(Debug, Clone, Default)]
+pub struct ConsoleAdmins(pub Vec<String>);
+
+impl ConsoleAdmins {
+    pub fn from_list(list: &str) -> ConsoleAdmins {
+        ConsoleAdmins(
+            list.split(',')
+                .map(str::trim)
+                .filter(|id| !id.is_empty())
+                .map(String::from)
+                .collect(),
+        )
+    }
+}
+
+pub struct ConsoleAdmin {
+    pub sub: String,
+}
+
+impl FromRequest for ConsoleAdmin {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
+        let req_clone = req.clone();
+        let authorized_user = AuthorizedUser::from_request(req, payload);
+        Box::pin(async move {
+            let authorized_user = authorized_user.await?;
+            let admins = match req_clone.app_data::<web::Data<ConsoleAdmins>>() {
+                Some(admins) => admins,
+                None => return Err(ErrorInternalServerError("Console admins not configured")),
+            };
+            match admins.0.contains(&authorized_user.sub) {
+                true => Ok(ConsoleAdmin {
+                    sub: authorized_user.sub,
+                }),
+                false => Err(ErrorForbidden("Not a console administrator")),
+            }
+        })
+    }
+}
@@ -0,0 +1,136 @@
+use crate::services::projects::ProjectService;
+use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::{dev, web, Error, FromRequest, HttpRequest};
+use futures::Future;
+use mongodb::bson::DateTime;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::pin::Pin;
+
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ApiKeyKind {
+    Publishable,
+    Secret,
+}
+
+impl ApiKeyKind {
+    fn prefix(&self) -> &'static str {
+        match self {
+            ApiKeyKind::Publishable => "sb_pk_",
+            ApiKeyKind::Secret => "sb_sk_",
+        }
+    }
+}
+
+/// Stored form of an API key, the key itself is only known by its hash.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectApiKey {
+    pub id: String,
+    pub name: String,
+    pub kind: ApiKeyKind,
+    pub prefix: String,
+    #[serde(skip_serializing)]
+    pub hash: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedApiKey {
+    pub key: String,
+    pub api_key: ProjectApiKey,
+}
+
+pub fn generate_api_key(kind: ApiKeyKind) -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{}{}", kind.prefix(), hex::encode(bytes))
+}
+
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Visible start of a key, used to tell keys apart in the console.
+pub fn display_prefix(key: &str) -> String {
+    key.chars().take(10).collect()
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct ProjectApiClient {
+    pub project_id: String,
+    pub key_id: String,
+    pub kind: ApiKeyKind,
+}
+
+impl ProjectApiClient {
+    pub fn get_key(req: &HttpRequest) -> Option<&str> {
+        req.headers()
+            .get("X-API-Key")
+            .or_else(|| req.headers().get("apikey"))
+            .and_then(|val| val.to_str().ok())
+    }
+}
+
+impl ProjectApiClient {
+    /// Checks that `key` is a current API key of `project_id`.
+    pub async fn authenticate(
+        req: &HttpRequest,
+        project_id: &str,
+        key: &str,
+    ) -> Result<ProjectApiClient, Error> {
+        let project_service = match req.app_data::<web::Data<ProjectService>>() {
+            Some(service) => service,
+            None => return Err(ErrorInternalServerError("Project service not configured")),
+        };
+        match project_service.get_api_key(project_id, key).await {
+            Ok(Some(api_key)) => Ok(ProjectApiClient {
+                project_id: project_id.into(),
+                key_id: api_key.id,
+                kind: api_key.kind,
+            }),
+            _ => Err(ErrorBadRequest("Not Authorized")),
+        }
+    }
+}
+
+impl FromRequest for ProjectApiClient {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        let req_clone = req.clone();
+        Box::pin(async move {
+            let project_id = match req_clone.match_info().get("project_id") {
+                Some(id) => id,
+                None => return Err(ErrorInternalServerError("No project id given for guard")),
+            };
+            let key = match ProjectApiClient::get_key(&req_clone) {
+                Some(key) => key,
+                None => return Err(ErrorBadRequest("Not Authorized")),
+            };
+            ProjectApiClient::authenticate(&req_clone, project_id, key).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_keys() {
+        let secret = generate_api_key(ApiKeyKind::Secret);
+        let publishable = generate_api_key(ApiKeyKind::Publishable);
+        assert!(secret.starts_with("sb_sk_"));
+        assert!(publishable.starts_with("sb_pk_"));
+        assert_ne!(secret, generate_api_key(ApiKeyKind::Secret));
+        assert_eq!(hash_api_key(&secret), hash_api_key(&secret));
+        assert_ne!(hash_api_key(&secret), secret);
+        assert_eq!(display_prefix(&secret).len(), 10);
+    }
+}
@@ -0,0 +1,162 @@
+use mongodb::bson::{Bson, Document};
+use mongodb::error::{Error, ErrorKind, WriteFailure, TRANSIENT_TRANSACTION_ERROR};
+use serde::{Deserialize, Serialize};
+
+/// Most operations or documents in one bulk request.
+pub const MAX_BULK_OPERATIONS: usize = 1000;
+
+/// Write of a bulk request, in the shape of MongoDB's `bulkWrite`. Updates
+/// set the fields of `update` like the update endpoints.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum BulkOperation {
+    InsertOne {
+        document: Document,
+    },
+    UpdateOne {
+        filter: Document,
+        update: Document,
+    },
+    UpdateMany {
+        filter: Document,
+        update: Document,
+    },
+    ReplaceOne {
+        filter: Document,
+        replacement: Document,
+    },
+    DeleteOne {
+        filter: Document,
+    },
+    DeleteMany {
+        filter: Document,
+    },
+}
+
+/// Write of a transaction, a bulk operation on a collection of the project
+/// database, e.g. `{"collection": "orders", "insertOne": {"document": {}}}`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct TransactionOperation {
+    pub collection: String,
+    #[serde(flatten)]
+    pub operation: BulkOperation,
+}
+
+/// Outcome of one operation, `error` tells why it failed.
+#[derive(Serialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkOperationResult {
+    pub index: usize,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inserted_id: Option<Bson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modified_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_count: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BulkOperationResult {
+    pub fn failed(index: usize, error: String) -> BulkOperationResult {
+        BulkOperationResult {
+            index,
+            ok: false,
+            error: Some(error),
+            ..Default::default()
+        }
+    }
+}
+
+/// Results of the attempted operations, in order. Ordered requests stop at
+/// the first failure, the operations after it are counted as `skipped`. For
+/// transactions `ok` tells whether it was committed, nothing is written
+/// otherwise.
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkWriteReport {
+    pub ok: bool,
+    pub results: Vec<BulkOperationResult>,
+    pub skipped: usize,
+}
+
+impl BulkWriteReport {
+    /// Report of `results` out of `count` operations.
+    pub fn new(mut results: Vec<BulkOperationResult>, count: usize) -> BulkWriteReport {
+        results.sort_by_key(|result| result.index);
+        BulkWriteReport {
+            ok: results.len() == count && results.iter().all(|result| result.ok),
+            skipped: count - results.len(),
+            results,
+        }
+    }
+}
+
+/// Message of a failed write shown to the client, server errors are not
+/// detailed.
+pub fn write_error_message(error: &Error) -> String {
+    if error.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+        return String::from("Write conflict, try again.");
+    }
+    match error.kind.as_ref() {
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.message.clone(),
+        ErrorKind::BulkWrite(failure) => failure
+            .write_errors
+            .as_ref()
+            .and_then(|errors| errors.first())
+            .map(|error| error.message.clone())
+            .unwrap_or_else(|| String::from("Failure writing documents.")),
+        _ => String::from("Failure writing documents."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn test_bulk_operations() {
+        let operations: Vec<BulkOperation> = serde_json::from_str(
+            r#"[
+                {"insertOne": {"document": {"name": "a"}}},
+                {"replaceOne": {"filter": {"name": "a"}, "replacement": {"name": "b"}}},
+                {"deleteMany": {"filter": {}}}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(operations.len(), 3);
+        assert!(
+            matches!(&operations[1], BulkOperation::ReplaceOne { replacement, .. }
+            if *replacement == doc! {"name": "b"})
+        );
+
+        let report = BulkWriteReport::new(
+            vec![
+                BulkOperationResult::failed(1, String::from("Permission denied.")),
+                BulkOperationResult {
+                    index: 0,
+                    ok: true,
+                    ..Default::default()
+                },
+            ],
+            3,
+        );
+        assert!(!report.ok);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.results[0].index, 0);
+
+        let operation: TransactionOperation = serde_json::from_str(
+            r#"{"collection": "orders", "deleteOne": {"filter": {"_id": 1}}}"#,
+        )
+        .unwrap();
+        assert_eq!(operation.collection, "orders");
+        assert!(
+            matches!(operation.operation, BulkOperation::DeleteOne { filter }
+            if filter == doc! {"_id": 1})
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use mongodb::bson::{doc, Bson, Document};
+use serde::Serialize;
+
+/// Change stream operations pushed to subscribers.
+const DOCUMENT_OPERATIONS: &[&str] = &["insert", "update", "replace", "delete"];
+
+/// Write to a watched collection as sent to subscribers. `document` is the
+/// document after the write, missing for deletions.
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEvent {
+    pub operation_type: String,
+    pub document_id: Bson,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<Document>,
+    /// Opaque position in the change stream to resume after this event.
+    pub resume_token: String,
+}
+
+impl ChangeEvent {
+    /// Event for a change stream entry, `None` for entries other than writes
+    /// to documents.
+    pub fn from_change(change: &Document) -> Option<ChangeEvent> {
+        let operation_type = change.get_str("operationType").ok()?;
+        if !DOCUMENT_OPERATIONS.contains(&operation_type) {
+            return None;
+        }
+        Some(ChangeEvent {
+            operation_type: String::from(operation_type),
+            document_id: change.get_document("documentKey").ok()?.get("_id")?.clone(),
+            document: change.get_document("fullDocument").ok().cloned(),
+            resume_token: encode_resume_token(change.get_document("_id").ok()?),
+        })
+    }
+}
+
+pub fn encode_resume_token(token: &Document) -> String {
+    let mut bytes = vec![];
+    token
+        .to_writer(&mut bytes)
+        .expect("documents can be serialized");
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+pub fn decode_resume_token(token: &str) -> Result<Document, String> {
+    let invalid = || String::from("Invalid resume token.");
+    let bytes = base64::decode_config(token, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+    Document::from_reader(&mut bytes.as_slice()).map_err(|_| invalid())
+}
+
+/// Rewrites a document filter to match the `fullDocument` of change stream
+/// entries. Only the `$and`, `$or` and `$nor` top level operators are kept.
+fn prefix_filter(filter: &Document) -> Result<Document, String> {
+    let mut prefixed = Document::new();
+    for (key, value) in filter {
+        match key.as_str() {
+            "$and" | "$or" | "$nor" => {
+                let filters = match value {
+                    Bson::Array(filters) => filters
+                        .iter()
+                        .map(|filter| match filter {
+                            Bson::Document(filter) => prefix_filter(filter).map(Bson::Document),
+                            _ => Err(format!("{} must be an array of filters.", key)),
+                        })
+                        .collect::<Result<Vec<Bson>, String>>()?,
+                    _ => return Err(format!("{} must be an array of filters.", key)),
+                };
+                prefixed.insert(key, filters);
+            }
+            operator if operator.starts_with('$') => {
+                return Err(format!(
+                    "The {} operator is not supported in subscriptions.",
+                    operator
+                ))
+            }
+            field => {
+                prefixed.insert(format!("fullDocument.{}", field), value.clone());
+            }
+        }
+    }
+    Ok(prefixed)
+}
+
+/// Pipeline of a change stream pushing the inserts, updates and
+/// replacements of documents matching `filter`, and every deletion when
+/// `deletes` is set since deleted documents cannot be matched.
+pub fn change_stream_pipeline(
+    filter: &Document,
+    deletes: bool,
+    resume_after: Option<Document>,
+) -> Result<Vec<Document>, String> {
+    let mut change_stream = doc! {"fullDocument": "updateLookup"};
+    if let Some(token) = resume_after {
+        change_stream.insert("resumeAfter", token);
+    }
+    let mut writes = prefix_filter(filter)?;
+    writes.insert(
+        "operationType",
+        doc! {"$in": ["insert", "update", "replace"]},
+    );
+    let mut branches = vec![writes];
+    if deletes {
+        branches.push(doc! {"operationType": "delete"});
+    }
+    Ok(vec![
+        doc! {"$changeStream": change_stream},
+        doc! {"$match": {"$or": branches}},
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_change_stream_pipeline() {
+        let filter = doc! {"$and": [{"status": {"$in": ["paid"]}}, {"owner": "user"}]};
+        let pipeline = change_stream_pipeline(&filter, false, None).unwrap();
+        assert_eq!(
+            pipeline[1],
+            doc! {"$match": {"$or": [{
+                "$and": [
+                    {"fullDocument.status": {"$in": ["paid"]}},
+                    {"fullDocument.owner": "user"},
+                ],
+                "operationType": {"$in": ["insert", "update", "replace"]},
+            }]}}
+        );
+
+        let token = doc! {"_data": "8261"};
+        let pipeline = change_stream_pipeline(&doc! {}, true, Some(token.clone())).unwrap();
+        assert_eq!(
+            pipeline[0],
+            doc! {"$changeStream": {"fullDocument": "updateLookup", "resumeAfter": token}}
+        );
+        assert_eq!(
+            pipeline[1]
+                .get_document("$match")
+                .unwrap()
+                .get_array("$or")
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(change_stream_pipeline(&doc! {"$where": "true"}, true, None).is_err());
+        assert!(change_stream_pipeline(&doc! {"$or": {"a": 1}}, true, None).is_err());
+    }
+
+    #[test]
+    fn test_change_event() {
+        let change = doc! {
+            "_id": {"_data": "8261"},
+            "operationType": "delete",
+            "documentKey": {"_id": 7},
+        };
+        let event = ChangeEvent::from_change(&change).unwrap();
+        assert_eq!(event.document_id, Bson::Int32(7));
+        assert_eq!(event.document, None);
+        assert_eq!(
+            decode_resume_token(&event.resume_token),
+            Ok(doc! {"_data": "8261"})
+        );
+        assert!(ChangeEvent::from_change(&doc! {"_id": {}, "operationType": "drop"}).is_none());
+        assert!(decode_resume_token("not a token").is_err());
+    }
+}
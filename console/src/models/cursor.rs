@@ -0,0 +1,273 @@
+use mongodb::bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+/// Page size when the query sets no limit.
+pub const DEFAULT_PAGE_SIZE: i64 = 100;
+/// Largest page returned, whatever the query limit.
+pub const MAX_PAGE_SIZE: i64 = 1000;
+
+/// Page of a document query, `next_cursor` continues after its last document
+/// and is `None` on the last page.
+#[derive(Deserialize, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentPage {
+    pub documents: Vec<Document>,
+    pub next_cursor: Option<String>,
+}
+
+/// Sort of a paginated query, one field followed by `_id` as tie breaker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorSort {
+    pub field: String,
+    pub direction: i32,
+}
+
+impl CursorSort {
+    /// Reads the sort of a query, which may only hold one field besides
+    /// `_id`. Without sort documents are returned by `_id`.
+    pub fn from_sort(sort: Option<&Document>) -> Result<CursorSort, String> {
+        let sort = match sort {
+            Some(sort) => sort,
+            None => {
+                return Ok(CursorSort {
+                    field: String::from("_id"),
+                    direction: 1,
+                })
+            }
+        };
+        let mut fields = sort.iter().filter(|(field, _)| field.as_str() != "_id");
+        let (field, direction) = match (fields.next(), fields.next()) {
+            (None, _) => match sort.get("_id") {
+                Some(direction) => ("_id", direction),
+                None => {
+                    return Ok(CursorSort {
+                        field: String::from("_id"),
+                        direction: 1,
+                    })
+                }
+            },
+            (Some((field, direction)), None) => (field.as_str(), direction),
+            (Some(_), Some(_)) => {
+                return Err(String::from("Queries can only be sorted by one field."))
+            }
+        };
+        let direction = match direction {
+            Bson::Int32(direction) => *direction as i64,
+            Bson::Int64(direction) => *direction,
+            Bson::Double(direction) => *direction as i64,
+            _ => return Err(String::from("Sort directions must be 1 or -1.")),
+        };
+        match direction {
+            1 | -1 => Ok(CursorSort {
+                field: String::from(field),
+                direction: direction as i32,
+            }),
+            _ => Err(String::from("Sort directions must be 1 or -1.")),
+        }
+    }
+
+    pub fn to_document(&self) -> Document {
+        match self.field.as_str() {
+            "_id" => doc! {"_id": self.direction},
+            field => doc! {field: self.direction, "_id": self.direction},
+        }
+    }
+
+    /// Checks that a projection returns the sort field and `_id` unchanged,
+    /// the cursor of the next page is read from them.
+    pub fn check_projection(&self, projection: &Document) -> Result<(), String> {
+        let flag = |value: &Bson| match value {
+            Bson::Boolean(flag) => Some(*flag),
+            Bson::Int32(flag) => Some(*flag != 0),
+            Bson::Int64(flag) => Some(*flag != 0),
+            Bson::Double(flag) => Some(*flag != 0.0),
+            _ => None,
+        };
+        // Whether `path` returns `field` whole, or touches it at all.
+        let covers =
+            |path: &str, field: &str| path == field || field.starts_with(&format!("{}.", path));
+        let touches = |path: &str, field: &str| {
+            covers(path, field) || path.starts_with(&format!("{}.", field))
+        };
+        let inclusion = projection
+            .iter()
+            .any(|(_, value)| flag(value) != Some(false));
+        let kept = |field: &str| {
+            let mut touching = projection
+                .iter()
+                .filter(|(path, _)| touches(path, field))
+                .peekable();
+            match touching.peek() {
+                None => !inclusion || field == "_id",
+                Some(_) => {
+                    touching.all(|(path, value)| covers(path, field) && flag(value) == Some(true))
+                }
+            }
+        };
+        match kept(&self.field) && kept("_id") {
+            true => Ok(()),
+            false => Err(String::from(
+                "Paginated queries must project the sort field and _id.",
+            )),
+        }
+    }
+}
+
+/// Position after a document, given to clients as an opaque string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentCursor {
+    pub sort: CursorSort,
+    pub value: Bson,
+    pub id: Bson,
+}
+
+/// Value at a dotted path of a document, `Null` when missing.
+fn get_path(document: &Document, path: &str) -> Bson {
+    let mut parts = path.split('.');
+    let mut value = match parts.next().and_then(|part| document.get(part)) {
+        Some(value) => value,
+        None => return Bson::Null,
+    };
+    for part in parts {
+        value = match value {
+            Bson::Document(document) => match document.get(part) {
+                Some(value) => value,
+                None => return Bson::Null,
+            },
+            _ => return Bson::Null,
+        };
+    }
+    value.clone()
+}
+
+impl DocumentCursor {
+    /// Cursor continuing after `document`.
+    pub fn after(sort: &CursorSort, document: &Document) -> DocumentCursor {
+        DocumentCursor {
+            sort: sort.clone(),
+            value: get_path(document, &sort.field),
+            id: document.get("_id").cloned().unwrap_or(Bson::Null),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let document = doc! {
+            "f": &self.sort.field,
+            "d": self.sort.direction,
+            "v": self.value.clone(),
+            "i": self.id.clone(),
+        };
+        let mut bytes = vec![];
+        document
+            .to_writer(&mut bytes)
+            .expect("documents can be serialized");
+        base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Result<DocumentCursor, String> {
+        let invalid = || String::from("Invalid cursor.");
+        let bytes =
+            base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).map_err(|_| invalid())?;
+        let document = Document::from_reader(&mut bytes.as_slice()).map_err(|_| invalid())?;
+        Ok(DocumentCursor {
+            sort: CursorSort {
+                field: String::from(document.get_str("f").map_err(|_| invalid())?),
+                direction: document.get_i32("d").map_err(|_| invalid())?,
+            },
+            value: document.get("v").cloned().ok_or_else(invalid)?,
+            id: document.get("i").cloned().ok_or_else(invalid)?,
+        })
+    }
+
+    /// Filter matching the documents after the cursor in its sort order.
+    /// Missing and null values sort before the others, which comparison
+    /// operators do not match.
+    pub fn filter(&self) -> Document {
+        let operator = match self.sort.direction {
+            1 => "$gt",
+            _ => "$lt",
+        };
+        let field = match self.sort.field.as_str() {
+            "_id" => return doc! {"_id": {operator: self.id.clone()}},
+            field => field,
+        };
+        let same_value = doc! {field: self.value.clone(), "_id": {operator: self.id.clone()}};
+        let branches = match (self.value == Bson::Null, self.sort.direction) {
+            (true, 1) => vec![doc! {field: {"$ne": Bson::Null}}, same_value],
+            (true, _) => vec![same_value],
+            (false, 1) => vec![doc! {field: {operator: self.value.clone()}}, same_value],
+            (false, _) => vec![
+                doc! {field: {operator: self.value.clone()}},
+                same_value,
+                doc! {field: Bson::Null},
+            ],
+        };
+        doc! {"$or": branches}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_sort() {
+        assert_eq!(
+            CursorSort::from_sort(Some(&doc! {"createdAt": -1, "_id": -1})),
+            Ok(CursorSort {
+                field: String::from("createdAt"),
+                direction: -1,
+            })
+        );
+        assert_eq!(
+            CursorSort::from_sort(None).unwrap().to_document(),
+            doc! {"_id": 1}
+        );
+        assert!(CursorSort::from_sort(Some(&doc! {"a": 1, "b": 1})).is_err());
+        assert!(CursorSort::from_sort(Some(&doc! {"a": 2})).is_err());
+    }
+
+    #[test]
+    fn test_check_projection() {
+        let sort = CursorSort::from_sort(Some(&doc! {"meta.rank": 1})).unwrap();
+        assert!(sort.check_projection(&doc! {}).is_ok());
+        assert!(sort.check_projection(&doc! {"name": 1, "meta": 1}).is_ok());
+        assert!(sort.check_projection(&doc! {"body": 0}).is_ok());
+        assert!(sort.check_projection(&doc! {"name": 1}).is_err());
+        assert!(sort.check_projection(&doc! {"_id": 1}).is_err());
+        assert!(sort.check_projection(&doc! {"meta": 0}).is_err());
+        assert!(sort
+            .check_projection(&doc! {"meta.rank": "$other"})
+            .is_err());
+        assert!(sort
+            .check_projection(&doc! {"meta.rank": 1, "_id": 0})
+            .is_err());
+
+        let sort = CursorSort::from_sort(None).unwrap();
+        assert!(sort.check_projection(&doc! {"name": 1}).is_ok());
+        assert!(sort.check_projection(&doc! {"_id": false}).is_err());
+    }
+
+    #[test]
+    fn test_document_cursor() {
+        let sort = CursorSort::from_sort(Some(&doc! {"meta.rank": 1})).unwrap();
+        let cursor = DocumentCursor::after(&sort, &doc! {"_id": 7, "meta": {"rank": 3}});
+        let decoded = DocumentCursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(decoded, cursor);
+        assert_eq!(
+            decoded.filter(),
+            doc! {"$or": [
+                {"meta.rank": {"$gt": 3}},
+                {"meta.rank": 3, "_id": {"$gt": 7}},
+            ]}
+        );
+        assert!(DocumentCursor::decode("not a cursor").is_err());
+
+        let sort = CursorSort::from_sort(Some(&doc! {"rank": -1})).unwrap();
+        let cursor = DocumentCursor::after(&sort, &doc! {"_id": 7});
+        assert_eq!(
+            cursor.filter(),
+            doc! {"$or": [{"rank": null, "_id": {"$lt": 7}}]}
+        );
+    }
+}
@@ -0,0 +1,34 @@
+/// Pages receiving the tokens mailed by the authentication flows, as a
+/// `?token=` query parameter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MailLinks {
+    pub verify_email: Option<String>,
+    pub reset_password: Option<String>,
+    pub magic_link: Option<String>,
+}
+
+impl MailLinks {
+    pub fn from_base_url(base_url: Option<&str>) -> MailLinks {
+        let base_url = base_url.map(|url| url.trim_end_matches('/'));
+        MailLinks {
+            verify_email: base_url.map(|url| format!("{}/verify-email", url)),
+            reset_password: base_url.map(|url| format!("{}/reset-password", url)),
+            magic_link: base_url.map(|url| format!("{}/magic-link", url)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_links_from_base_url() {
+        let links = MailLinks::from_base_url(Some("https://app.example.com/"));
+        assert_eq!(
+            links.verify_email.as_deref(),
+            Some("https://app.example.com/verify-email")
+        );
+        assert_eq!(MailLinks::from_base_url(None), MailLinks::default());
+    }
+}
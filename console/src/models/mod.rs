@@ -1 +1,9 @@
+pub mod admin;
+pub mod api_key;
+pub mod bulk;
+pub mod changes;
+pub mod cursor;
+pub mod mail;
+pub mod pipeline;
 pub mod project;
+pub mod rules;
@@ -0,0 +1,136 @@
+use mongodb::bson::{Bson, Document};
+
+/// Stages allowed in project pipelines. Stages writing collections, like
+/// `$out` and `$merge`, or reading server state are left out.
+const ALLOWED_STAGES: &[&str] = &[
+    "$addFields",
+    "$bucket",
+    "$bucketAuto",
+    "$count",
+    "$facet",
+    "$graphLookup",
+    "$group",
+    "$limit",
+    "$lookup",
+    "$match",
+    "$project",
+    "$redact",
+    "$replaceRoot",
+    "$replaceWith",
+    "$sample",
+    "$set",
+    "$skip",
+    "$sort",
+    "$sortByCount",
+    "$unionWith",
+    "$unset",
+    "$unwind",
+];
+
+fn get_pipeline<'a>(value: &'a Bson, stage: &str) -> Result<Vec<&'a Document>, String> {
+    match value {
+        Bson::Array(stages) => stages
+            .iter()
+            .map(|stage| match stage {
+                Bson::Document(stage) => Ok(stage),
+                _ => Err(String::from("Pipeline stages must be documents.")),
+            })
+            .collect(),
+        _ => Err(format!("The pipeline of {} must be an array.", stage)),
+    }
+}
+
+/// Name of a collection read by a stage, which must be a plain name so it
+/// is looked up in the project database.
+fn get_collection(value: Option<&Bson>, stage: &str) -> Result<String, String> {
+    match value {
+        Some(Bson::String(collection)) if !collection.is_empty() => Ok(collection.clone()),
+        _ => Err(format!(
+            "{} must name a collection of the project database.",
+            stage
+        )),
+    }
+}
+
+fn check_stages(pipeline: &[&Document], collections: &mut Vec<String>) -> Result<(), String> {
+    for stage in pipeline {
+        let (name, value) = match (stage.len(), stage.iter().next()) {
+            (1, Some(entry)) => entry,
+            _ => return Err(String::from("Pipeline stages must hold one operator.")),
+        };
+        let name = name.as_str();
+        if !ALLOWED_STAGES.contains(&name) {
+            return Err(format!("The {} stage is not allowed.", name));
+        }
+        let options = match value {
+            Bson::Document(options) => Some(options),
+            _ => None,
+        };
+        match (name, options) {
+            ("$lookup", Some(options)) | ("$graphLookup", Some(options)) => {
+                collections.push(get_collection(options.get("from"), name)?);
+                if let Some(pipeline) = options.get("pipeline") {
+                    check_stages(&get_pipeline(pipeline, name)?, collections)?;
+                }
+            }
+            ("$unionWith", Some(options)) => {
+                collections.push(get_collection(options.get("coll"), name)?);
+                if let Some(pipeline) = options.get("pipeline") {
+                    check_stages(&get_pipeline(pipeline, name)?, collections)?;
+                }
+            }
+            ("$unionWith", None) => collections.push(get_collection(Some(value), name)?),
+            ("$facet", Some(options)) => {
+                for (_, pipeline) in options {
+                    check_stages(&get_pipeline(pipeline, name)?, collections)?;
+                }
+            }
+            ("$lookup", None) | ("$graphLookup", None) | ("$facet", None) => {
+                return Err(format!("The {} stage must be a document.", name))
+            }
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Checks a pipeline against the stage allowlist, nested pipelines
+/// included, and returns the other collections it reads.
+pub fn check_pipeline(pipeline: &[Document]) -> Result<Vec<String>, String> {
+    let mut collections = vec![];
+    check_stages(&pipeline.iter().collect::<Vec<_>>(), &mut collections)?;
+    collections.sort();
+    collections.dedup();
+    Ok(collections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::bson::doc;
+
+    #[test]
+    fn test_check_pipeline() {
+        let pipeline = vec![
+            doc! {"$match": {"status": "paid"}},
+            doc! {"$lookup": {
+                "from": "users",
+                "as": "user",
+                "pipeline": [{"$unionWith": "admins"}],
+            }},
+            doc! {"$facet": {"total": [{"$count": "count"}]}},
+        ];
+        assert_eq!(
+            check_pipeline(&pipeline),
+            Ok(vec![String::from("admins"), String::from("users")])
+        );
+        assert!(check_pipeline(&[doc! {"$out": "copy"}]).is_err());
+        assert!(check_pipeline(&[doc! {"$facet": {"a": [{"$merge": "copy"}]}}]).is_err());
+        assert!(check_pipeline(&[doc! {"$lookup": {
+            "from": {"db": "project-other", "coll": "users"},
+            "as": "user",
+        }}])
+        .is_err());
+        assert!(check_pipeline(&[doc! {"$match": {}, "$limit": 1}]).is_err());
+    }
+}
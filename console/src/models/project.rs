@@ -1,12 +1,17 @@
+use crate::models::api_key::{ApiKeyKind, ProjectApiClient, ProjectApiKey};
+use crate::models::mail::MailLinks;
+use crate::models::rules::RuleCaller;
+use crate::services::project_auth::ProjectAuthService;
 use crate::services::projects::ProjectService;
-use actix_web::error::{ErrorBadRequest, ErrorInternalServerError};
+use actix_web::error::{ErrorBadRequest, ErrorForbidden, ErrorInternalServerError, ErrorNotFound};
 use actix_web::{dev, web, Error, FromRequest, HttpRequest};
-use auth::models::users::Claims;
+use auth::models::oidc::OidcProvider;
+use auth::models::users::{Claims, User};
 use auth::services::AuthenticationService;
 use futures::Future;
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
-use mongodb::bson::doc;
+use mongodb::bson::{doc, DateTime, Document};
 use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
 use std::pin::Pin;
 use validator::Validate;
 
@@ -16,18 +21,306 @@ pub struct Project {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     pub id: Option<mongodb::bson::oid::ObjectId>,
     pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     #[serde(default)]
-    pub users: Vec<String>,
+    pub settings: Document,
+    #[serde(default)]
+    pub users: Vec<ProjectMember>,
+    #[serde(default)]
+    pub invitations: Vec<ProjectInvitation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime>,
+    /// Managed through `ProjectService`, never serialized back out.
+    #[serde(default, skip_serializing)]
+    pub api_keys: Vec<ProjectApiKey>,
+    /// Managed through `ProjectService`, never serialized back out.
+    #[serde(default, skip_serializing)]
+    pub auth_providers: Vec<OidcProvider>,
 }
 
-#[derive(Deserialize, Debug, Serialize, Validate)]
-pub struct ProjectUser {
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectUpdate {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub settings: Option<Document>,
+}
+
+impl Project {
+    /// Links of the mails sent to the project users, built from the
+    /// `siteUrl` setting.
+    pub fn get_mail_links(&self) -> MailLinks {
+        MailLinks::from_base_url(self.settings.get_str("siteUrl").ok())
+    }
+
+    /// Guest sessions are opt-in with the `allowAnonymousSignIn` setting.
+    pub fn allows_anonymous_sign_in(&self) -> bool {
+        self.settings
+            .get_bool("allowAnonymousSignIn")
+            .unwrap_or(false)
+    }
+
+    /// App metadata keys embedded in the user tokens, from the
+    /// `tokenClaims` setting.
+    pub fn get_token_claims(&self) -> Vec<String> {
+        self.settings
+            .get_array("tokenClaims")
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|key| key.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn get_auth_provider(&self, name: &str) -> Option<&OidcProvider> {
+        self.auth_providers
+            .iter()
+            .find(|provider| provider.name == name)
+    }
+
+    pub fn get_role(&self, user_id: &str) -> Option<ProjectRole> {
+        self.users
+            .iter()
+            .find(|member| member.user_id == user_id)
+            .map(|member| member.role)
+    }
+
+    pub fn get_invitation(&self, user_id: &str) -> Option<&ProjectInvitation> {
+        self.invitations
+            .iter()
+            .find(|invitation| invitation.user_id == user_id)
+    }
+
+    /// Checks whether giving `user_id` the role `new_role`, or removing them
+    /// when `None`, would leave the project without any owner.
+    pub fn would_remove_last_owner(&self, user_id: &str, new_role: Option<ProjectRole>) -> bool {
+        if self.get_role(user_id) != Some(ProjectRole::Owner)
+            || new_role == Some(ProjectRole::Owner)
+        {
+            return false;
+        }
+        self.users
+            .iter()
+            .filter(|member| member.role == ProjectRole::Owner)
+            .count()
+            <= 1
+    }
+}
+
+/// Roles are ordered from the least to the most privileged.
+#[derive(Deserialize, Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum ProjectRole {
+    Viewer,
+    Developer,
+    Admin,
+    Owner,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase", from = "ProjectMemberEntry")]
+pub struct ProjectMember {
+    pub user_id: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInvitation {
+    pub user_id: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInvitationSummary {
+    pub project_id: String,
+    pub project_name: String,
+    pub role: ProjectRole,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectMemberProfile {
+    pub user_id: String,
+    pub role: ProjectRole,
+    pub profile: Option<User>,
+}
+
+/// Projects created before roles existed store members as plain user ids,
+/// those members were the project creators.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProjectMemberEntry {
+    Member {
+        #[serde(rename = "userId")]
+        user_id: String,
+        role: ProjectRole,
+    },
+    Legacy(String),
+}
+
+impl From<ProjectMemberEntry> for ProjectMember {
+    fn from(entry: ProjectMemberEntry) -> Self {
+        match entry {
+            ProjectMemberEntry::Member { user_id, role } => ProjectMember { user_id, role },
+            ProjectMemberEntry::Legacy(user_id) => ProjectMember {
+                user_id,
+                role: ProjectRole::Owner,
+            },
+        }
+    }
+}
+
+pub trait ProjectAccess {
+    const ROLE: ProjectRole;
+}
+
+pub enum ViewerAccess {}
+pub enum AdminAccess {}
+pub enum OwnerAccess {}
+
+impl ProjectAccess for ViewerAccess {
+    const ROLE: ProjectRole = ProjectRole::Viewer;
+}
+
+impl ProjectAccess for AdminAccess {
+    const ROLE: ProjectRole = ProjectRole::Admin;
+}
+
+impl ProjectAccess for OwnerAccess {
+    const ROLE: ProjectRole = ProjectRole::Owner;
+}
+
+/// Credentials of a request to the project API, its `Authorization` header
+/// and API key.
+#[derive(Debug, Clone, Default)]
+pub struct Credentials {
+    pub authorization: Option<String>,
+    pub api_key: Option<String>,
+}
+
+impl Credentials {
+    pub fn from_request(req: &HttpRequest) -> Result<Credentials, Error> {
+        let authorization = match req.headers().get("Authorization") {
+            Some(value) => Some(String::from(value.to_str().map_err(ErrorBadRequest)?)),
+            None => None,
+        };
+        Ok(Credentials {
+            authorization,
+            api_key: ProjectApiClient::get_key(req).map(String::from),
+        })
+    }
+}
+
+fn get_project_id(req: &HttpRequest) -> Result<String, Error> {
+    match req.match_info().get("project_id") {
+        Some(id) => Ok(String::from(id)),
+        None => Err(ErrorInternalServerError("No project id given for guard")),
+    }
+}
+
+/// Refuses projects waiting to be purged after a soft delete.
+async fn ensure_project_active(req: &HttpRequest, project_id: &str) -> Result<(), Error> {
+    let project_service = match req.app_data::<web::Data<ProjectService>>() {
+        Some(service) => service,
+        None => return Err(ErrorInternalServerError("Project service not configured")),
+    };
+    match project_service.is_active(project_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ErrorNotFound("No project found")),
+        Err(error) => Err(ErrorInternalServerError(error)),
+    }
+}
+
+/// Console user with at least the role required by `A` on the project
+/// given in the route.
+#[derive(Deserialize, Debug, Serialize)]
+pub struct ProjectUser<A: ProjectAccess = ViewerAccess> {
     pub token: String,
     pub sub: String,
     pub project: Project,
+    pub role: ProjectRole,
+    /// Expiry of the token, in seconds since the epoch.
+    pub exp: usize,
+    #[serde(skip)]
+    access: PhantomData<A>,
+}
+
+impl<A: ProjectAccess> ProjectUser<A> {
+    /// Checks the console token of `authorization` for access to
+    /// `project_id`.
+    pub async fn authenticate(
+        req: &HttpRequest,
+        project_id: &str,
+        authorization: &str,
+    ) -> Result<ProjectUser<A>, Error> {
+        let my_slice: Vec<&str> = authorization.split(' ').collect();
+        let auth_service = match req.app_data::<web::Data<AuthenticationService>>() {
+            Some(service) => service,
+            None => return Err(ErrorInternalServerError("Authentication not configured")),
+        };
+        if my_slice.len() != 2 {
+            return Err(ErrorBadRequest("Bad Headers"));
+        }
+
+        let token_claims_res = auth_service.keys.decode::<Claims>(my_slice[1], None);
+
+        if token_claims_res.is_err() {
+            return Err(ErrorBadRequest("Not Authorized"));
+        }
+
+        let claims = token_claims_res.unwrap();
+        if claims.aud.is_some() {
+            return Err(ErrorBadRequest("Not Authorized"));
+        }
+        let sid = match claims.sid {
+            Some(sid) => sid,
+            None => return Err(ErrorBadRequest("Not Authorized")),
+        };
+        match auth_service.sessions.is_active(&sid).await {
+            Ok(true) => (),
+            _ => return Err(ErrorBadRequest("Session revoked")),
+        }
+        if let Err(error) = auth_service.sessions.touch(&sid).await {
+            println!("{}", error);
+        }
+        if auth_service.status_cache.is_some() {
+            match auth_service.is_user_active(&claims.sub).await {
+                Ok(true) => (),
+                _ => return Err(ErrorBadRequest("Account disabled")),
+            }
+        }
+        let project_service = match req.app_data::<web::Data<ProjectService>>() {
+            Some(service) => service,
+            None => return Err(ErrorInternalServerError("Project service not configured")),
+        };
+
+        let project = match project_service
+            .get_user_access_to_project(project_id, &claims.sub)
+            .await
+        {
+            Ok(Some(project)) => project,
+            _ => return Err(ErrorInternalServerError("No project access")),
+        };
+        let role = match project.get_role(&claims.sub) {
+            Some(role) if role >= A::ROLE => role,
+            _ => return Err(ErrorForbidden("Insufficient project role")),
+        };
+        Ok(ProjectUser {
+            token: String::from(authorization),
+            sub: claims.sub,
+            project,
+            role,
+            exp: claims.exp,
+            access: PhantomData,
+        })
+    }
 }
 
-impl FromRequest for ProjectUser {
+impl<A: ProjectAccess + 'static> FromRequest for ProjectUser<A> {
     type Error = Error;
     type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
     type Config = ();
@@ -35,64 +328,197 @@ impl FromRequest for ProjectUser {
     fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
         let req_clone = req.clone();
         Box::pin(async move {
-            let project_id = match req_clone.match_info().get("project_id") {
-                Some(id) => id,
-                None => return Err(ErrorInternalServerError("No project id given for guard")),
-            };
-            match req_clone.headers().get("Authorization") {
-                Some(val) => match val.to_str() {
-                    Ok(v) => {
-                        let my_slice: Vec<&str> = v.split(" ").collect();
-                        let auth_service =
-                            match req_clone.app_data::<web::Data<AuthenticationService>>() {
-                                Some(service) => service,
-                                None => {
-                                    return Err(ErrorInternalServerError(
-                                        "Authentication not configured",
-                                    ))
-                                }
-                            };
-                        let key = DecodingKey::from_secret(auth_service.secret.as_ref());
-
-                        if my_slice.len() != 2 {
-                            return Err(ErrorBadRequest("Bad Headers"));
-                        }
-
-                        let token_claims_res =
-                            decode::<Claims>(my_slice[1], &key, &Validation::new(Algorithm::HS256));
-
-                        if token_claims_res.is_err() {
-                            return Err(ErrorBadRequest("Not Authorized"));
-                        }
-
-                        let claims = token_claims_res.unwrap().claims;
-                        let project_service =
-                            match req_clone.app_data::<web::Data<ProjectService>>() {
-                                Some(service) => service,
-                                None => {
-                                    return Err(ErrorInternalServerError(
-                                        "Project service not configured",
-                                    ))
-                                }
-                            };
-
-                        let authorized_user = match project_service
-                            .get_user_access_to_project(project_id, &claims.sub)
-                            .await
-                        {
-                            Ok(Some(project)) => ProjectUser {
-                                token: v.into(),
-                                sub: claims.sub,
-                                project: project,
-                            },
-                            _ => return Err(ErrorInternalServerError("No project access")),
-                        };
-                        Ok(authorized_user)
-                    }
-                    Err(e) => Err(ErrorBadRequest(e)),
-                },
+            let project_id = get_project_id(&req_clone)?;
+            match Credentials::from_request(&req_clone)?.authorization {
+                Some(authorization) => {
+                    ProjectUser::authenticate(&req_clone, &project_id, &authorization).await
+                }
                 None => Err(ErrorBadRequest("Not Authorized")),
             }
         })
     }
 }
+
+#[derive(Deserialize, Debug, Serialize, Validate)]
+pub struct ProjectEndUser {
+    pub token: String,
+    pub sub: String,
+    pub sid: String,
+    pub project_id: String,
+    /// Custom claims of the token.
+    pub app_metadata: Document,
+    /// Expiry of the token, in seconds since the epoch.
+    pub exp: usize,
+}
+
+impl ProjectEndUser {
+    /// Checks the project token of `authorization` for `project_id`.
+    pub async fn authenticate(
+        req: &HttpRequest,
+        project_id: &str,
+        authorization: &str,
+    ) -> Result<ProjectEndUser, Error> {
+        ensure_project_active(req, project_id).await?;
+        let my_slice: Vec<&str> = authorization.split(' ').collect();
+        let service = match req.app_data::<web::Data<ProjectAuthService>>() {
+            Some(service) => service,
+            None => {
+                return Err(ErrorInternalServerError(
+                    "Project authentication not configured",
+                ))
+            }
+        };
+        if my_slice.len() != 2 {
+            return Err(ErrorBadRequest("Bad Headers"));
+        }
+
+        let token_claims_res = service
+            .keys
+            .get(project_id)
+            .decode::<Claims>(my_slice[1], Some(project_id));
+
+        if token_claims_res.is_err() {
+            return Err(ErrorBadRequest("Not Authorized"));
+        }
+
+        let claims = token_claims_res.unwrap();
+        let sid = match claims.sid {
+            Some(sid) => sid,
+            None => return Err(ErrorBadRequest("Not Authorized")),
+        };
+        match service.is_session_active(project_id, &sid).await {
+            Ok(true) => (),
+            _ => return Err(ErrorBadRequest("Session revoked")),
+        }
+        if let Err(error) = service.touch_session(project_id, &sid).await {
+            println!("{}", error);
+        }
+        if service.is_strict() {
+            match service.is_user_active(project_id, &claims.sub).await {
+                Ok(true) => (),
+                _ => return Err(ErrorBadRequest("Account disabled")),
+            }
+        }
+        Ok(ProjectEndUser {
+            token: String::from(authorization),
+            sub: claims.sub,
+            sid,
+            project_id: String::from(project_id),
+            app_metadata: claims.app_metadata.unwrap_or_default(),
+            exp: claims.exp,
+        })
+    }
+}
+
+impl FromRequest for ProjectEndUser {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        let req_clone = req.clone();
+        Box::pin(async move {
+            let project_id = get_project_id(&req_clone)?;
+            match Credentials::from_request(&req_clone)?.authorization {
+                Some(authorization) => {
+                    ProjectEndUser::authenticate(&req_clone, &project_id, &authorization).await
+                }
+                None => Err(ErrorBadRequest("Not Authorized")),
+            }
+        })
+    }
+}
+
+/// Resolves who is calling the project data API with `credentials`, along
+/// with the expiry of their token, `None` for API keys and anonymous
+/// callers. Secret API keys act as a project admin, publishable keys only
+/// identify the project so the bearer token, if any, decides between an end
+/// user and a console member.
+pub async fn authenticate_caller(
+    req: &HttpRequest,
+    project_id: &str,
+    credentials: &Credentials,
+) -> Result<(RuleCaller, Option<usize>), Error> {
+    if let Some(key) = &credentials.api_key {
+        let api_client = ProjectApiClient::authenticate(req, project_id, key).await?;
+        if api_client.kind == ApiKeyKind::Secret {
+            return Ok((RuleCaller::Member(ProjectRole::Admin), None));
+        }
+    }
+    let authorization = match &credentials.authorization {
+        Some(authorization) => authorization,
+        None => {
+            ensure_project_active(req, project_id).await?;
+            return Ok((RuleCaller::Anonymous, None));
+        }
+    };
+    if let Ok(end_user) = ProjectEndUser::authenticate(req, project_id, authorization).await {
+        let caller = RuleCaller::User {
+            sub: end_user.sub,
+            claims: end_user.app_metadata,
+        };
+        return Ok((caller, Some(end_user.exp)));
+    }
+    ProjectUser::<ViewerAccess>::authenticate(req, project_id, authorization)
+        .await
+        .map(|project_user| {
+            (
+                RuleCaller::Member(project_user.role),
+                Some(project_user.exp),
+            )
+        })
+}
+
+impl FromRequest for RuleCaller {
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut dev::Payload) -> Self::Future {
+        let req_clone = req.clone();
+        Box::pin(async move {
+            let project_id = get_project_id(&req_clone)?;
+            let credentials = Credentials::from_request(&req_clone)?;
+            authenticate_caller(&req_clone, &project_id, &credentials)
+                .await
+                .map(|(caller, _)| caller)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_legacy_members_are_owners() {
+        let project: Project = mongodb::bson::from_document(doc! {
+            "name": "project",
+            "users": ["legacy", {"userId": "viewer", "role": "viewer"}],
+        })
+        .unwrap();
+        assert_eq!(project.get_role("legacy"), Some(ProjectRole::Owner));
+        assert_eq!(project.get_role("viewer"), Some(ProjectRole::Viewer));
+        assert_eq!(project.get_role("other"), None);
+        assert!(ProjectRole::Admin >= ProjectRole::Developer);
+    }
+
+    #[test]
+    fn test_last_owner_guard() {
+        let mut project: Project = mongodb::bson::from_document(doc! {
+            "name": "project",
+            "users": [
+                {"userId": "owner", "role": "owner"},
+                {"userId": "admin", "role": "admin"},
+            ],
+        })
+        .unwrap();
+        assert!(project.would_remove_last_owner("owner", None));
+        assert!(project.would_remove_last_owner("owner", Some(ProjectRole::Admin)));
+        assert!(!project.would_remove_last_owner("owner", Some(ProjectRole::Owner)));
+        assert!(!project.would_remove_last_owner("admin", None));
+
+        project.users[1].role = ProjectRole::Owner;
+        assert!(!project.would_remove_last_owner("owner", None));
+    }
+}
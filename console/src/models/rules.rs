@@ -0,0 +1,409 @@
+use crate::models::project::ProjectRole;
+use mongodb::bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug, Serialize, Clone, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum RuleCondition {
+    #[default]
+    Deny,
+    Anonymous,
+    Authenticated,
+    Owner(String),
+    /// Users whose token claim `name` equals `value`, or contains it when
+    /// the claim is an array.
+    Claim {
+        name: String,
+        value: Bson,
+    },
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+pub struct CollectionRules {
+    #[serde(default)]
+    pub read: RuleCondition,
+    #[serde(default)]
+    pub create: RuleCondition,
+    #[serde(default)]
+    pub update: RuleCondition,
+    #[serde(default)]
+    pub delete: RuleCondition,
+}
+
+#[derive(Deserialize, Debug, Serialize, Clone, Default)]
+pub struct ProjectRules {
+    #[serde(default)]
+    pub collections: HashMap<String, CollectionRules>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleOperation {
+    Read,
+    Create,
+    Update,
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleCaller {
+    Member(ProjectRole),
+    Anonymous,
+    User { sub: String, claims: Document },
+}
+
+impl RuleCaller {
+    pub fn has_role(&self, required: ProjectRole) -> bool {
+        match self {
+            RuleCaller::Member(role) => *role >= required,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuleDecision {
+    Allow,
+    Deny,
+    Owner { field: String, sub: String },
+}
+
+/// Collections prefixed with `_` hold the rules and the project users. They
+/// are only reached through their dedicated endpoints.
+pub fn is_reserved_collection(collection_name: &str) -> bool {
+    collection_name.starts_with('_')
+}
+
+impl ProjectRules {
+    /// Evaluates the rules of a collection for a caller. Project members
+    /// bypass the rules, viewers may only read. Reserved collections are
+    /// denied to every caller.
+    pub fn authorize(
+        &self,
+        collection_name: &str,
+        operation: RuleOperation,
+        caller: &RuleCaller,
+    ) -> RuleDecision {
+        if is_reserved_collection(collection_name) {
+            return RuleDecision::Deny;
+        }
+        if let RuleCaller::Member(role) = caller {
+            return match operation == RuleOperation::Read || *role >= ProjectRole::Developer {
+                true => RuleDecision::Allow,
+                false => RuleDecision::Deny,
+            };
+        }
+        let rules = match self.collections.get(collection_name) {
+            Some(rules) => rules,
+            None => return RuleDecision::Deny,
+        };
+        let condition = match operation {
+            RuleOperation::Read => &rules.read,
+            RuleOperation::Create => &rules.create,
+            RuleOperation::Update => &rules.update,
+            RuleOperation::Delete => &rules.delete,
+        };
+        match (condition, caller) {
+            (RuleCondition::Anonymous, _) => RuleDecision::Allow,
+            (RuleCondition::Authenticated, RuleCaller::User { .. }) => RuleDecision::Allow,
+            (RuleCondition::Owner(field), RuleCaller::User { sub, .. }) => RuleDecision::Owner {
+                field: field.clone(),
+                sub: sub.clone(),
+            },
+            (RuleCondition::Claim { name, value }, RuleCaller::User { claims, .. }) => {
+                match claims.get(name) {
+                    Some(Bson::Array(values)) if values.contains(value) => RuleDecision::Allow,
+                    Some(claim) if claim == value => RuleDecision::Allow,
+                    _ => RuleDecision::Deny,
+                }
+            }
+            _ => RuleDecision::Deny,
+        }
+    }
+
+    /// Checks that an upsert may insert `document` under the create rule.
+    /// The fields it sets take precedence over the filter in the inserted
+    /// document, so they must hold the owner field.
+    pub fn allows_upsert(
+        &self,
+        collection_name: &str,
+        caller: &RuleCaller,
+        document: &Document,
+    ) -> bool {
+        self.authorize(collection_name, RuleOperation::Create, caller)
+            .allows_document(document, true)
+    }
+}
+
+/// Value at a dotted path of a document.
+fn get_path<'a>(document: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut parts = path.split('.');
+    let mut value = document.get(parts.next()?)?;
+    for part in parts {
+        value = match value {
+            Bson::Document(document) => document.get(part)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+impl RuleDecision {
+    /// Restricts a query filter to the documents the decision grants access to.
+    pub fn restrict_filter(&self, filter: Option<Document>) -> Option<Document> {
+        match self {
+            RuleDecision::Allow => Some(filter.unwrap_or_default()),
+            RuleDecision::Deny => None,
+            RuleDecision::Owner { field, sub } => {
+                let owner_filter = doc! { field.as_str(): sub.as_str() };
+                match filter {
+                    Some(filter) if !filter.is_empty() => {
+                        Some(doc! {"$and": [filter, owner_filter]})
+                    }
+                    _ => Some(owner_filter),
+                }
+            }
+        }
+    }
+
+    /// Checks that a written document keeps the owner field pointing to the
+    /// caller, the field may be a dotted path. When `required` is false the
+    /// document is a set of updated fields: a missing owner field is
+    /// accepted but its parents cannot be replaced.
+    pub fn allows_document(&self, document: &Document, required: bool) -> bool {
+        let (field, sub) = match self {
+            RuleDecision::Allow => return true,
+            RuleDecision::Deny => return false,
+            RuleDecision::Owner { field, sub } => (field, sub),
+        };
+        let owner = Bson::String(sub.clone());
+        let mut found = false;
+        for (key, value) in document {
+            if key == field {
+                if *value != owner {
+                    return false;
+                }
+                found = true;
+            } else if let Some(path) = field.strip_prefix(&format!("{}.", key)) {
+                match value {
+                    Bson::Document(value) if required && get_path(value, path) == Some(&owner) => {
+                        found = true
+                    }
+                    _ => return false,
+                }
+            } else if key.starts_with(&format!("{}.", field)) {
+                return false;
+            }
+        }
+        found || !required
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_rules() -> ProjectRules {
+        let mut collections = HashMap::new();
+        collections.insert(
+            String::from("posts"),
+            CollectionRules {
+                read: RuleCondition::Anonymous,
+                create: RuleCondition::Authenticated,
+                update: RuleCondition::Owner(String::from("authorId")),
+                delete: RuleCondition::Deny,
+            },
+        );
+        ProjectRules { collections }
+    }
+
+    #[test]
+    fn test_members_bypass_rules() {
+        let rules = ProjectRules::default();
+        let developer = RuleCaller::Member(ProjectRole::Developer);
+        let viewer = RuleCaller::Member(ProjectRole::Viewer);
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Delete, &developer),
+            RuleDecision::Allow
+        );
+        assert_eq!(
+            rules.authorize("_auth", RuleOperation::Read, &viewer),
+            RuleDecision::Deny
+        );
+        assert_eq!(
+            rules.authorize("_rules", RuleOperation::Update, &developer),
+            RuleDecision::Deny
+        );
+        assert_eq!(
+            rules.authorize(
+                "_auth_mfa",
+                RuleOperation::Read,
+                &RuleCaller::Member(ProjectRole::Owner)
+            ),
+            RuleDecision::Deny
+        );
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Read, &viewer),
+            RuleDecision::Allow
+        );
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Update, &viewer),
+            RuleDecision::Deny
+        );
+    }
+
+    #[test]
+    fn test_reserved_and_unknown_collections_denied() {
+        let rules = get_rules();
+        let caller = RuleCaller::User {
+            sub: String::from("user"),
+            claims: Document::new(),
+        };
+        assert_eq!(
+            rules.authorize("_auth", RuleOperation::Read, &caller),
+            RuleDecision::Deny
+        );
+        assert_eq!(
+            rules.authorize("comments", RuleOperation::Read, &caller),
+            RuleDecision::Deny
+        );
+    }
+
+    #[test]
+    fn test_conditions() {
+        let rules = get_rules();
+        let user = RuleCaller::User {
+            sub: String::from("user"),
+            claims: Document::new(),
+        };
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Read, &RuleCaller::Anonymous),
+            RuleDecision::Allow
+        );
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Create, &RuleCaller::Anonymous),
+            RuleDecision::Deny
+        );
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Create, &user),
+            RuleDecision::Allow
+        );
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Update, &user),
+            RuleDecision::Owner {
+                field: String::from("authorId"),
+                sub: String::from("user"),
+            }
+        );
+        assert_eq!(
+            rules.authorize("posts", RuleOperation::Delete, &user),
+            RuleDecision::Deny
+        );
+    }
+
+    #[test]
+    fn test_claim_condition() {
+        let rules: ProjectRules = mongodb::bson::from_document(doc! {
+            "collections": {
+                "reports": {"read": {"claim": {"name": "roles", "value": "editor"}}}
+            }
+        })
+        .unwrap();
+        let editor = RuleCaller::User {
+            sub: String::from("user"),
+            claims: doc! {"roles": ["viewer", "editor"]},
+        };
+        let viewer = RuleCaller::User {
+            sub: String::from("user"),
+            claims: doc! {"roles": "viewer"},
+        };
+        assert_eq!(
+            rules.authorize("reports", RuleOperation::Read, &editor),
+            RuleDecision::Allow
+        );
+        assert_eq!(
+            rules.authorize("reports", RuleOperation::Read, &viewer),
+            RuleDecision::Deny
+        );
+        assert_eq!(
+            rules.authorize("reports", RuleOperation::Read, &RuleCaller::Anonymous),
+            RuleDecision::Deny
+        );
+    }
+
+    #[test]
+    fn test_upsert_needs_create_rule() {
+        let mut rules = get_rules();
+        let user = RuleCaller::User {
+            sub: String::from("user"),
+            claims: Document::new(),
+        };
+        assert!(rules.allows_upsert("posts", &user, &doc! {"title": "a"}));
+        assert!(!rules.allows_upsert("posts", &RuleCaller::Anonymous, &doc! {"title": "a"}));
+        rules.collections.get_mut("posts").unwrap().create =
+            RuleCondition::Owner(String::from("authorId"));
+        assert!(rules.allows_upsert("posts", &user, &doc! {"authorId": "user"}));
+        assert!(!rules.allows_upsert("posts", &user, &doc! {"title": "a"}));
+        assert!(!rules.allows_upsert("posts", &user, &doc! {"authorId": "other"}));
+        assert!(rules.allows_upsert(
+            "posts",
+            &RuleCaller::Member(ProjectRole::Developer),
+            &doc! {}
+        ));
+    }
+
+    #[test]
+    fn test_owner_restricts_filter_and_documents() {
+        let decision = RuleDecision::Owner {
+            field: String::from("authorId"),
+            sub: String::from("user"),
+        };
+        assert_eq!(
+            decision.restrict_filter(None),
+            Some(doc! {"authorId": "user"})
+        );
+        assert_eq!(
+            decision.restrict_filter(Some(doc! {"title": "a"})),
+            Some(doc! {"$and": [{"title": "a"}, {"authorId": "user"}]})
+        );
+        assert!(decision.allows_document(&doc! {"authorId": "user"}, true));
+        assert!(!decision.allows_document(&doc! {"authorId": "other"}, false));
+        assert!(!decision.allows_document(&doc! {}, true));
+        assert!(decision.allows_document(&doc! {}, false));
+    }
+
+    #[test]
+    fn test_dotted_owner_field() {
+        let decision = RuleDecision::Owner {
+            field: String::from("meta.owner"),
+            sub: String::from("user"),
+        };
+        assert_eq!(
+            decision.restrict_filter(None),
+            Some(doc! {"meta.owner": "user"})
+        );
+        assert!(decision.allows_document(&doc! {"meta": {"owner": "user", "tag": 1}}, true));
+        assert!(!decision.allows_document(&doc! {"meta": {"owner": "other"}}, true));
+        assert!(!decision.allows_document(&doc! {"meta": {}}, true));
+        assert!(!decision.allows_document(&doc! {"meta": "user"}, true));
+        assert!(decision.allows_document(&doc! {"meta.owner": "user"}, false));
+        assert!(decision.allows_document(&doc! {"meta.tag": 1}, false));
+        assert!(!decision.allows_document(&doc! {"meta": {"owner": "other"}}, false));
+        assert!(!decision.allows_document(&doc! {"meta": {"owner": "user"}}, false));
+        assert!(!decision.allows_document(&doc! {"meta.owner.id": "other"}, false));
+    }
+
+    #[test]
+    fn test_rules_deserialize() {
+        let rules: ProjectRules = mongodb::bson::from_document(doc! {
+            "collections": {
+                "posts": {"read": "anonymous", "update": {"owner": "authorId"}}
+            }
+        })
+        .unwrap();
+        let posts = rules.collections.get("posts").unwrap();
+        assert_eq!(posts.read, RuleCondition::Anonymous);
+        assert_eq!(posts.update, RuleCondition::Owner(String::from("authorId")));
+        assert_eq!(posts.delete, RuleCondition::Deny);
+    }
+}
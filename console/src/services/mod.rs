@@ -1,3 +1,4 @@
 pub mod project_auth;
 pub mod project_mongodb;
 pub mod projects;
+pub mod tickets;
@@ -1,37 +1,487 @@
-use auth::{models::users::User, services::AuthenticationService};
-use error::{SBError, SBResult};
-use mongodb::{bson::Document, options::FindOptions, Client};
+use crate::services::projects::ProjectService;
+use auth::{
+    http,
+    mailer::Mailer,
+    models::{
+        oidc::OidcProvider,
+        sessions::{ClientInfo, SessionInfo},
+        users::{AccountStatus, AdminUpdateUser, User, UserPage},
+    },
+    services::{
+        keys::KeyStore, password_hashing::PasswordHashing, password_policy::PasswordPolicy,
+        status_cache::StatusCache, users::MarshalledInsertOne, AuthenticationService,
+    },
+};
+use error::SBResult;
+use jsonwebtoken::jwk::JwkSet;
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+    Client,
+};
+use std::collections::HashMap;
+
+/// Keys signing the tokens of each project. Projects without keys of their
+/// own share `default`, their tokens only differ by issuer and audience.
+#[derive(Clone, Default)]
+pub struct ProjectKeys {
+    pub default: KeyStore,
+    pub projects: HashMap<String, KeyStore>,
+    /// Prefix of the issuers, a project issues `{issuer}/projects/{id}`.
+    pub issuer: String,
+}
+
+impl ProjectKeys {
+    /// Keys of a project, issuing and accepting tokens of its issuer only.
+    pub fn get(&self, project_id: &str) -> KeyStore {
+        self.projects
+            .get(project_id)
+            .unwrap_or(&self.default)
+            .with_issuer(&format!("{}/projects/{}", self.issuer, project_id))
+    }
+
+    /// Public keys of every project.
+    pub fn jwks(&self) -> JwkSet {
+        let mut jwks = self.default.jwks();
+        for keys in self.projects.values() {
+            jwks.keys.extend(keys.jwks().keys);
+        }
+        jwks
+    }
+}
 
 #[derive(Clone)]
 pub struct ProjectAuthService {
     client: Client,
-    secret: String,
+    pub keys: ProjectKeys,
+    pub password_policy: PasswordPolicy,
+    pub password_hashing: PasswordHashing,
+    projects: ProjectService,
+    status_cache: Option<StatusCache>,
+    http_client: Option<http::Client>,
 }
 
 impl ProjectAuthService {
-    pub fn new(client: Client, secret: String) -> ProjectAuthService {
+    pub fn new(
+        client: Client,
+        keys: ProjectKeys,
+        password_policy: PasswordPolicy,
+        password_hashing: PasswordHashing,
+        projects: ProjectService,
+    ) -> ProjectAuthService {
         ProjectAuthService {
-            client: client,
-            secret: secret,
+            client,
+            keys,
+            password_policy,
+            password_hashing,
+            projects,
+            status_cache: None,
+            http_client: None,
         }
     }
 
+    /// Shares `client`, and the provider documents it caches, between the
+    /// social logins of every project.
+    pub fn with_http_client(mut self, client: http::Client) -> ProjectAuthService {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Enables strict mode, the account status of project users is checked
+    /// on every request and cached in `cache`.
+    pub fn with_status_cache(mut self, cache: StatusCache) -> ProjectAuthService {
+        self.status_cache = Some(cache);
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.status_cache.is_some()
+    }
+
+    fn get_authentication_service(&self, project_id: &str) -> AuthenticationService {
+        let database = self.client.database(&format!("project-{}", project_id));
+        let service = AuthenticationService::init(
+            database,
+            String::from("_auth"),
+            self.keys.get(project_id),
+            self.password_policy.clone(),
+            self.password_hashing,
+        );
+        let service = match &self.status_cache {
+            Some(cache) => service.with_status_cache(cache.clone()),
+            None => service,
+        };
+        match &self.http_client {
+            Some(client) => service.with_http_client(client.clone()),
+            None => service,
+        }
+    }
+
+    /// Authentication service issuing tokens with the custom claims selected
+    /// by the project.
+    async fn get_token_service(&self, project_id: &str) -> SBResult<AuthenticationService> {
+        let project = self.projects.get(project_id).await?;
+        Ok(self
+            .get_authentication_service(project_id)
+            .with_custom_claims(project.get_token_claims()))
+    }
+
     pub async fn get_users(
         &self,
         project_id: &str,
         filter: Option<Document>,
         options: Option<FindOptions>,
     ) -> SBResult<Vec<User>> {
-        let database = self.client.database(&format!("project-{}", project_id));
-        let service =
-            AuthenticationService::init(database, String::from("_auth"), self.secret.clone());
-        service
+        self.get_authentication_service(project_id)
             .users
             .get_users(filter, options)
             .await
-            .map_err(|_| SBError::InternalServiceError {
-                service: String::from("project_auth"),
-                message: String::from("Failure listing users."),
-            })
+    }
+
+    pub async fn create_user(&self, project_id: &str, user: User) -> SBResult<MarshalledInsertOne> {
+        self.get_authentication_service(project_id)
+            .users
+            .create(user)
+            .await
+    }
+
+    pub async fn authenticate(
+        &self,
+        project_id: &str,
+        email: &str,
+        password: &str,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        self.get_token_service(project_id)
+            .await?
+            .authenticate(email, password, Some(String::from(project_id)), client)
+            .await
+    }
+
+    pub async fn unlock(&self, project_id: &str, email: &str) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .unlock(email)
+            .await
+    }
+
+    pub async fn refresh(&self, project_id: &str, refresh_token: &str) -> SBResult<Document> {
+        self.get_token_service(project_id)
+            .await?
+            .refresh(refresh_token, Some(String::from(project_id)))
+            .await
+    }
+
+    pub async fn logout(&self, project_id: &str, session_id: &str) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .logout(session_id)
+            .await
+    }
+
+    pub async fn is_session_active(&self, project_id: &str, session_id: &str) -> SBResult<bool> {
+        self.get_authentication_service(project_id)
+            .sessions
+            .is_active(session_id)
+            .await
+    }
+
+    pub async fn touch_session(&self, project_id: &str, session_id: &str) -> SBResult<()> {
+        self.get_authentication_service(project_id)
+            .sessions
+            .touch(session_id)
+            .await
+    }
+
+    pub async fn list_sessions(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        session_id: &str,
+    ) -> SBResult<Vec<SessionInfo>> {
+        self.get_authentication_service(project_id)
+            .list_sessions(user_id, session_id)
+            .await
+    }
+
+    pub async fn revoke_session(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        session_id: &str,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .revoke_session(user_id, session_id)
+            .await
+    }
+
+    pub async fn revoke_other_sessions(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        session_id: &str,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .revoke_other_sessions(user_id, session_id)
+            .await
+    }
+
+    pub async fn get_user(&self, project_id: &str, user_id: &str) -> SBResult<User> {
+        self.get_authentication_service(project_id)
+            .users
+            .get(user_id)
+            .await
+    }
+
+    pub async fn request_email_verification(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .request_email_verification(user_id, mailer, link)
+            .await
+    }
+
+    pub async fn verify_email(&self, project_id: &str, token: &str) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .verify_email(token)
+            .await
+    }
+
+    pub async fn request_password_reset(
+        &self,
+        project_id: &str,
+        email: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .request_password_reset(email, mailer, link)
+            .await
+    }
+
+    pub async fn reset_password(
+        &self,
+        project_id: &str,
+        token: &str,
+        password: &str,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .reset_password(token, password)
+            .await
+    }
+
+    pub async fn change_password(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        session_id: &str,
+        current_password: &str,
+        new_password: &str,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .change_password(user_id, session_id, current_password, new_password)
+            .await
+    }
+
+    pub async fn start_oidc(
+        &self,
+        project_id: &str,
+        provider: &OidcProvider,
+        redirect_uri: &str,
+        link_user_id: Option<&str>,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .start_oidc(provider, redirect_uri, link_user_id)
+            .await
+    }
+
+    pub async fn finish_oidc(
+        &self,
+        project_id: &str,
+        providers: &[OidcProvider],
+        state: &str,
+        code: &str,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        self.get_token_service(project_id)
+            .await?
+            .finish_oidc(
+                providers,
+                state,
+                code,
+                Some(String::from(project_id)),
+                client,
+            )
+            .await
+    }
+
+    pub async fn sign_in_anonymously(
+        &self,
+        project_id: &str,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        self.get_token_service(project_id)
+            .await?
+            .sign_in_anonymously(Some(String::from(project_id)), client)
+            .await
+    }
+
+    pub async fn upgrade_anonymous(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        email: &str,
+        password: &str,
+        username: Option<&str>,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .upgrade_anonymous(user_id, email, password, username)
+            .await
+    }
+
+    pub async fn request_magic_link(
+        &self,
+        project_id: &str,
+        email: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .request_magic_link(email, mailer, link)
+            .await
+    }
+
+    pub async fn sign_in_with_magic_link(
+        &self,
+        project_id: &str,
+        token: &str,
+        client: &ClientInfo,
+    ) -> SBResult<Document> {
+        self.get_token_service(project_id)
+            .await?
+            .sign_in_with_magic_link(token, Some(String::from(project_id)), client)
+            .await
+    }
+
+    pub async fn update_user_metadata(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        updates: &Document,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .users
+            .update_user_metadata(user_id, updates)
+            .await
+            .map(|_| doc! {"success": true})
+    }
+
+    pub async fn update_app_metadata(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        updates: &Document,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .users
+            .update_app_metadata(user_id, updates)
+            .await
+            .map(|_| doc! {"success": true})
+    }
+
+    pub async fn search_users(
+        &self,
+        project_id: &str,
+        query: Option<&str>,
+        page: u64,
+        per_page: i64,
+    ) -> SBResult<UserPage> {
+        self.get_authentication_service(project_id)
+            .users
+            .search(query, page, per_page)
+            .await
+    }
+
+    pub async fn admin_update_user(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        updates: AdminUpdateUser,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .users
+            .admin_update(user_id, updates)
+            .await
+    }
+
+    pub async fn set_user_status(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        status: &AccountStatus,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .set_status(user_id, status)
+            .await
+    }
+
+    pub async fn is_user_active(&self, project_id: &str, user_id: &str) -> SBResult<bool> {
+        self.get_authentication_service(project_id)
+            .is_user_active(user_id)
+            .await
+    }
+
+    pub async fn force_password_reset(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        mailer: &dyn Mailer,
+        link: Option<&str>,
+    ) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .force_password_reset(user_id, mailer, link)
+            .await
+    }
+
+    pub async fn delete_user(&self, project_id: &str, user_id: &str) -> SBResult<Document> {
+        self.get_authentication_service(project_id)
+            .delete_user(user_id)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use auth::models::users::Claims;
+
+    #[test]
+    fn test_project_keys() {
+        let mut projects = HashMap::new();
+        projects.insert(String::from("own"), KeyStore::from_secret("own"));
+        let keys = ProjectKeys {
+            default: KeyStore::from_secret("shared"),
+            projects,
+            issuer: String::from("snellbaas"),
+        };
+        let claims = Claims {
+            sub: String::from("user"),
+            exp: 32503680000,
+            aud: Some(String::from("a")),
+            sid: None,
+            app_metadata: None,
+        };
+        let token = keys.get("a").encode(&claims).unwrap();
+        assert!(keys.get("a").decode::<Claims>(&token, Some("a")).is_ok());
+        // Same shared key, other issuer.
+        assert!(keys.get("b").decode::<Claims>(&token, Some("a")).is_err());
+        assert!(keys.get("own").decode::<Claims>(&token, Some("a")).is_err());
+        let token = keys.get("own").encode(&claims).unwrap();
+        assert!(KeyStore::from_secret("shared")
+            .with_issuer("snellbaas/projects/own")
+            .decode::<Claims>(&token, Some("a"))
+            .is_err());
     }
 }
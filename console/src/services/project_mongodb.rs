@@ -1,14 +1,29 @@
+use crate::models::bulk::{
+    write_error_message, BulkOperation, BulkOperationResult, BulkWriteReport, TransactionOperation,
+    MAX_BULK_OPERATIONS,
+};
+use crate::models::changes::{change_stream_pipeline, decode_resume_token};
+use crate::models::cursor::{
+    CursorSort, DocumentCursor, DocumentPage, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE,
+};
+use crate::models::pipeline::check_pipeline;
+use crate::models::project::ProjectRole;
+use crate::models::rules::{
+    is_reserved_collection, ProjectRules, RuleCaller, RuleDecision, RuleOperation,
+};
 use error::{SBError, SBResult};
 use futures::TryStreamExt;
 use mongodb::bson::oid::ObjectId;
+use mongodb::error::{ErrorKind, TRANSIENT_TRANSACTION_ERROR};
 use mongodb::{
-    bson::{doc, Document},
+    bson::{doc, Bson, Document},
     options::{
-        CreateCollectionOptions, DeleteOptions, DropCollectionOptions, FindOneOptions, FindOptions,
-        InsertOneOptions, ReplaceOptions, UpdateModifications, UpdateOptions,
+        AggregateOptions, CreateCollectionOptions, DeleteOptions, DropCollectionOptions,
+        FindOneOptions, FindOptions, InsertManyOptions, InsertOneOptions, ReplaceOptions,
+        UpdateModifications, UpdateOptions,
     },
     results::{CollectionSpecification, DeleteResult, UpdateResult},
-    Client,
+    Client, ClientSession, Collection, Cursor, Database,
 };
 use std::str::FromStr;
 
@@ -19,14 +34,136 @@ pub struct ProjectMongoDBService {
 
 impl ProjectMongoDBService {
     pub fn new(client: Client) -> ProjectMongoDBService {
-        ProjectMongoDBService { client: client }
+        ProjectMongoDBService { client }
+    }
+
+    fn get_database(&self, project_id: &str) -> Database {
+        self.client.database(&format!("project-{}", project_id))
+    }
+
+    fn permission_denied() -> SBError {
+        SBError::ServiceError {
+            service: String::from("mongodb"),
+            message: String::from("Permission denied."),
+        }
+    }
+
+    fn require_role(caller: &RuleCaller, role: ProjectRole) -> SBResult<()> {
+        match caller.has_role(role) {
+            true => Ok(()),
+            false => Err(ProjectMongoDBService::permission_denied()),
+        }
+    }
+
+    pub async fn drop_project_database(&self, project_id: &str) -> SBResult<()> {
+        self.get_database(project_id)
+            .drop(None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mongodb"),
+                message: String::from("Failure dropping project database."),
+            })
+    }
+
+    pub async fn get_rules(&self, project_id: &str) -> SBResult<ProjectRules> {
+        self.get_database(project_id)
+            .collection::<ProjectRules>("_rules")
+            .find_one(None, None)
+            .await
+            .map(|r| r.unwrap_or_default())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mongodb"),
+                message: String::from("Failure loading rules."),
+            })
+    }
+
+    pub async fn set_rules(&self, project_id: &str, rules: ProjectRules) -> SBResult<()> {
+        self.get_database(project_id)
+            .collection::<ProjectRules>("_rules")
+            .replace_one(
+                doc! {},
+                rules,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mongodb"),
+                message: String::from("Failure saving rules."),
+            })
+    }
+
+    /// Rules applying to a caller, project members bypass them.
+    async fn get_caller_rules(
+        &self,
+        project_id: &str,
+        caller: &RuleCaller,
+    ) -> SBResult<ProjectRules> {
+        match caller {
+            RuleCaller::Member(_) => Ok(ProjectRules::default()),
+            _ => self.get_rules(project_id).await,
+        }
+    }
+
+    /// Decision of the project rules on an operation, denials are errors.
+    pub async fn authorize(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        operation: RuleOperation,
+        caller: &RuleCaller,
+    ) -> SBResult<RuleDecision> {
+        let rules = self.get_caller_rules(project_id, caller).await?;
+        match rules.authorize(collection_name, operation, caller) {
+            RuleDecision::Deny => Err(ProjectMongoDBService::permission_denied()),
+            decision => Ok(decision),
+        }
+    }
+
+    async fn authorize_filter(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        operation: RuleOperation,
+        caller: &RuleCaller,
+        filter: Option<Document>,
+    ) -> SBResult<(RuleDecision, Document)> {
+        let decision = self
+            .authorize(project_id, collection_name, operation, caller)
+            .await?;
+        match decision.restrict_filter(filter) {
+            Some(filter) => Ok((decision, filter)),
+            None => Err(ProjectMongoDBService::permission_denied()),
+        }
+    }
+
+    /// Checks a write against the create rule when it may insert `document`
+    /// as an upsert, an update rule alone does not allow creating documents.
+    async fn authorize_upsert(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        caller: &RuleCaller,
+        upsert: Option<bool>,
+        document: &Document,
+    ) -> SBResult<()> {
+        if upsert != Some(true) {
+            return Ok(());
+        }
+        let rules = self.get_caller_rules(project_id, caller).await?;
+        match rules.allows_upsert(collection_name, caller, document) {
+            true => Ok(()),
+            false => Err(ProjectMongoDBService::permission_denied()),
+        }
     }
 
     pub async fn get_collections_for_project(
         &self,
         project_id: &str,
+        caller: &RuleCaller,
     ) -> SBResult<Vec<CollectionSpecification>> {
-        let database = self.client.database(&format!("project-{}", project_id));
+        ProjectMongoDBService::require_role(caller, ProjectRole::Viewer)?;
+        let database = self.get_database(project_id);
         let cursor = database
             .list_collections(doc! {"name": {"$regex": "^(?!_)"}}, None)
             .await
@@ -43,28 +180,259 @@ impl ProjectMongoDBService {
             })
     }
 
+    /// Adds the sort, limit and cursor position of a paginated query. The
+    /// limit is capped to `MAX_PAGE_SIZE` when `capped`, one more document
+    /// is fetched to know if there is a next page. Pages cannot skip
+    /// documents nor project away the fields their cursor is read from.
+    fn paginate(
+        filter: Document,
+        options: Option<FindOptions>,
+        cursor: Option<&str>,
+        capped: bool,
+    ) -> SBResult<(Document, FindOptions, CursorSort, Option<i64>)> {
+        let invalid = |message: String| SBError::ServiceError {
+            service: String::from("mongodb"),
+            message,
+        };
+        let mut options = options.unwrap_or_default();
+        let sort = CursorSort::from_sort(options.sort.as_ref()).map_err(invalid)?;
+        if capped {
+            if options.skip.is_some() {
+                return Err(invalid(String::from(
+                    "Paginated queries cannot skip documents, use the cursor.",
+                )));
+            }
+            if let Some(projection) = &options.projection {
+                sort.check_projection(projection).map_err(invalid)?;
+            }
+        }
+        let filter = match cursor {
+            Some(cursor) => {
+                let cursor = DocumentCursor::decode(cursor).map_err(invalid)?;
+                if cursor.sort != sort {
+                    return Err(invalid(String::from(
+                        "The cursor does not match the query sort.",
+                    )));
+                }
+                match filter.is_empty() {
+                    true => cursor.filter(),
+                    false => doc! {"$and": [filter, cursor.filter()]},
+                }
+            }
+            None => filter,
+        };
+        let page_size = match (options.limit.map(i64::abs), capped) {
+            (Some(limit), true) => Some(limit.clamp(1, MAX_PAGE_SIZE)),
+            (None, true) => Some(DEFAULT_PAGE_SIZE),
+            (limit, false) => limit.filter(|limit| *limit > 0),
+        };
+        options.sort = Some(sort.to_document());
+        options.limit = page_size.map(|page_size| page_size + 1);
+        Ok((filter, options, sort, page_size))
+    }
+
+    async fn find_documents(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        filter: Document,
+        options: FindOptions,
+    ) -> SBResult<Cursor<Document>> {
+        self.get_database(project_id)
+            .collection(collection_name)
+            .find(filter, options)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mongodb"),
+                message: String::from("Failure querying documents."),
+            })
+    }
+
+    /// Returns a page of the documents, at most `MAX_PAGE_SIZE`. The next
+    /// page is read by passing back its `next_cursor`.
     pub async fn get_documents_from_collection(
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         filter: Option<Document>,
         options: Option<FindOptions>,
-    ) -> SBResult<Vec<Document>> {
-        let database = self.client.database(&format!("project-{}", project_id));
-        let cursor = database
-            .collection(collection_name)
-            .find(filter, options)
+        cursor: Option<&str>,
+    ) -> SBResult<DocumentPage> {
+        let (_, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Read,
+                caller,
+                filter,
+            )
+            .await?;
+        let (filter, options, sort, page_size) =
+            ProjectMongoDBService::paginate(filter, options, cursor, true)?;
+        let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE) as usize;
+        let mut documents = self
+            .find_documents(project_id, collection_name, filter, options)
+            .await?
+            .try_collect::<Vec<Document>>()
             .await
             .map_err(|_| SBError::InternalServiceError {
                 service: String::from("mongodb"),
                 message: String::from("Failure querying documents."),
             })?;
+        let next_cursor = match documents.len() > page_size {
+            true => {
+                documents.truncate(page_size);
+                documents
+                    .last()
+                    .map(|document| DocumentCursor::after(&sort, document).encode())
+            }
+            false => None,
+        };
+        Ok(DocumentPage {
+            documents,
+            next_cursor,
+        })
+    }
+
+    /// Opens a MongoDB cursor over the documents for streaming, without
+    /// page size limit.
+    pub async fn stream_documents_from_collection(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        caller: &RuleCaller,
+        filter: Option<Document>,
+        options: Option<FindOptions>,
+        cursor: Option<&str>,
+    ) -> SBResult<Cursor<Document>> {
+        let (_, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Read,
+                caller,
+                filter,
+            )
+            .await?;
+        let (filter, mut options, _, limit) =
+            ProjectMongoDBService::paginate(filter, options, cursor, false)?;
+        options.limit = limit;
+        self.find_documents(project_id, collection_name, filter, options)
+            .await
+    }
+
+    /// Error of a pipeline MongoDB refused. Its details may describe data
+    /// the caller cannot read, they are only logged.
+    fn pipeline_error(error: mongodb::error::Error) -> SBError {
+        println!("{}", error);
+        SBError::ServiceError {
+            service: String::from("mongodb"),
+            message: String::from("Failure running the pipeline."),
+        }
+    }
+
+    /// Runs an aggregation pipeline, rejecting stages that write or leave
+    /// the project database. Callers restricted to their own documents only
+    /// aggregate those, and other collections must be fully readable.
+    /// Results are capped to `MAX_PAGE_SIZE` documents.
+    pub async fn aggregate(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        caller: &RuleCaller,
+        pipeline: Vec<Document>,
+        options: Option<AggregateOptions>,
+    ) -> SBResult<Vec<Document>> {
+        let collections = check_pipeline(&pipeline).map_err(|message| SBError::ServiceError {
+            service: String::from("mongodb"),
+            message,
+        })?;
+        let (_, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Read,
+                caller,
+                None,
+            )
+            .await?;
+        for collection in &collections {
+            let decision = self
+                .authorize(project_id, collection, RuleOperation::Read, caller)
+                .await?;
+            if decision != RuleDecision::Allow {
+                return Err(ProjectMongoDBService::permission_denied());
+            }
+        }
+        let mut stages = vec![];
+        if !filter.is_empty() {
+            stages.push(doc! {"$match": filter});
+        }
+        stages.extend(pipeline);
+        stages.push(doc! {"$limit": MAX_PAGE_SIZE});
+        let cursor = self
+            .get_database(project_id)
+            .collection::<Document>(collection_name)
+            .aggregate(stages, options)
+            .await
+            .map_err(ProjectMongoDBService::pipeline_error)?;
         cursor
             .try_collect::<Vec<Document>>()
             .await
-            .map_err(|_| SBError::InternalServiceError {
+            .map_err(ProjectMongoDBService::pipeline_error)
+    }
+
+    /// Opens a change stream on a collection pushing the writes the caller
+    /// may read that match `filter`. Callers restricted to their own
+    /// documents get no deletions, the owner of a deleted document is
+    /// unknown. Returns the read decision the stream was opened with.
+    pub async fn watch_collection(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        caller: &RuleCaller,
+        filter: Option<Document>,
+        resume_after: Option<&str>,
+    ) -> SBResult<(RuleDecision, Cursor<Document>)> {
+        let (decision, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Read,
+                caller,
+                filter,
+            )
+            .await?;
+        let pipeline = resume_after
+            .map(decode_resume_token)
+            .transpose()
+            .and_then(|resume_after| {
+                change_stream_pipeline(
+                    &filter,
+                    matches!(decision, RuleDecision::Allow),
+                    resume_after,
+                )
+            })
+            .map_err(|message| SBError::ServiceError {
                 service: String::from("mongodb"),
-                message: String::from("Failure querying documents."),
+                message,
+            })?;
+        self.get_database(project_id)
+            .collection::<Document>(collection_name)
+            .aggregate(pipeline, None)
+            .await
+            .map(|cursor| (decision, cursor))
+            .map_err(|error| match error.kind.as_ref() {
+                // Change streams are only opened on replica sets.
+                ErrorKind::Command(error) if error.code == 40573 => SBError::ServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Subscriptions need a replica set."),
+                },
+                _ => SBError::InternalServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Failure watching collection."),
+                },
             })
     }
 
@@ -72,6 +440,7 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         document_id: &str,
         options: Option<FindOneOptions>,
     ) -> SBResult<Option<Document>> {
@@ -79,10 +448,19 @@ impl ProjectMongoDBService {
             service: String::from("mongodb"),
             message: String::from("Failure making oid object."),
         })?;
-        let database = self.client.database(&format!("project-{}", project_id));
+        let (_, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Read,
+                caller,
+                Some(doc! {"_id": oid}),
+            )
+            .await?;
+        let database = self.get_database(project_id);
         database
             .collection(collection_name)
-            .find_one(doc! {"_id": oid}, options)
+            .find_one(filter, options)
             .await
             .map_err(|_| SBError::InternalServiceError {
                 service: String::from("mongodb"),
@@ -94,10 +472,17 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         document: Document,
         options: Option<InsertOneOptions>,
     ) -> SBResult<Document> {
-        let database = self.client.database(&format!("project-{}", project_id));
+        let decision = self
+            .authorize(project_id, collection_name, RuleOperation::Create, caller)
+            .await?;
+        if !decision.allows_document(&document, true) {
+            return Err(ProjectMongoDBService::permission_denied());
+        }
+        let database = self.get_database(project_id);
         database
             .collection(collection_name)
             .insert_one(document, options)
@@ -113,13 +498,347 @@ impl ProjectMongoDBService {
             })
     }
 
+    fn check_bulk_size(count: usize) -> SBResult<()> {
+        match count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("mongodb"),
+                message: String::from("Nothing to write."),
+            }),
+            count if count > MAX_BULK_OPERATIONS => Err(SBError::ServiceError {
+                service: String::from("mongodb"),
+                message: format!(
+                    "At most {} documents or operations can be written at once.",
+                    MAX_BULK_OPERATIONS
+                ),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Inserts many documents in one call. Ordered inserts stop at the first
+    /// failed document, unordered ones go on with the others.
+    pub async fn insert_documents(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        caller: &RuleCaller,
+        documents: Vec<Document>,
+        ordered: bool,
+    ) -> SBResult<BulkWriteReport> {
+        ProjectMongoDBService::check_bulk_size(documents.len())?;
+        let count = documents.len();
+        let decision = self
+            .authorize(project_id, collection_name, RuleOperation::Create, caller)
+            .await?;
+        let mut denied = vec![];
+        let mut indices = vec![];
+        let mut allowed = vec![];
+        for (index, mut document) in documents.into_iter().enumerate() {
+            if !decision.allows_document(&document, true) {
+                denied.push(BulkOperationResult::failed(
+                    index,
+                    String::from("Permission denied."),
+                ));
+                match ordered {
+                    true => break,
+                    false => continue,
+                }
+            }
+            if !document.contains_key("_id") {
+                document.insert("_id", ObjectId::new());
+            }
+            indices.push(index);
+            allowed.push(document);
+        }
+        if allowed.is_empty() {
+            return Ok(BulkWriteReport::new(denied, count));
+        }
+
+        let ids: Vec<Bson> = allowed
+            .iter()
+            .map(|document| document.get("_id").cloned().unwrap_or(Bson::Null))
+            .collect();
+        let options = InsertManyOptions::builder().ordered(ordered).build();
+        let failures: Vec<(usize, String)> = match self
+            .get_database(project_id)
+            .collection::<Document>(collection_name)
+            .insert_many(allowed, options)
+            .await
+        {
+            Ok(_) => vec![],
+            Err(error) => match error.kind.as_ref() {
+                ErrorKind::BulkWrite(failure) => failure
+                    .write_errors
+                    .iter()
+                    .flatten()
+                    .map(|error| (error.index, error.message.clone()))
+                    .collect(),
+                _ => {
+                    return Err(SBError::InternalServiceError {
+                        service: String::from("mongodb"),
+                        message: String::from("Failure creating documents."),
+                    })
+                }
+            },
+        };
+
+        let first_failure = failures.iter().map(|(position, _)| *position).min();
+        let mut results = vec![];
+        for (position, (index, id)) in indices.into_iter().zip(ids).enumerate() {
+            if ordered && first_failure.is_some_and(|first| position > first) {
+                break;
+            }
+            results.push(
+                match failures.iter().find(|(failed, _)| *failed == position) {
+                    Some((_, message)) => BulkOperationResult::failed(index, message.clone()),
+                    None => BulkOperationResult {
+                        index,
+                        ok: true,
+                        inserted_id: Some(id),
+                        ..Default::default()
+                    },
+                },
+            );
+        }
+        if !ordered || first_failure.is_none() {
+            results.extend(denied);
+        }
+        Ok(BulkWriteReport::new(results, count))
+    }
+
+    async fn start_session(&self) -> SBResult<ClientSession> {
+        self.client
+            .start_session(None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("mongodb"),
+                message: String::from("Failure starting session."),
+            })
+    }
+
+    async fn run_bulk_operation(
+        collection: &Collection<Document>,
+        rules: &ProjectRules,
+        caller: &RuleCaller,
+        session: &mut ClientSession,
+        index: usize,
+        operation: BulkOperation,
+    ) -> BulkOperationResult {
+        let kind = match &operation {
+            BulkOperation::InsertOne { .. } => RuleOperation::Create,
+            BulkOperation::UpdateOne { .. }
+            | BulkOperation::UpdateMany { .. }
+            | BulkOperation::ReplaceOne { .. } => RuleOperation::Update,
+            BulkOperation::DeleteOne { .. } | BulkOperation::DeleteMany { .. } => {
+                RuleOperation::Delete
+            }
+        };
+        let decision = rules.authorize(collection.name(), kind, caller);
+        let denied = BulkOperationResult::failed(index, String::from("Permission denied."));
+        let updated = |matched_count: u64, modified_count: u64| BulkOperationResult {
+            index,
+            ok: true,
+            matched_count: Some(matched_count),
+            modified_count: Some(modified_count),
+            ..Default::default()
+        };
+        let deleted = |deleted_count: u64| BulkOperationResult {
+            index,
+            ok: true,
+            deleted_count: Some(deleted_count),
+            ..Default::default()
+        };
+        let many = matches!(operation, BulkOperation::UpdateMany { .. });
+        let result = match operation {
+            BulkOperation::InsertOne { document } => {
+                if !decision.allows_document(&document, true) {
+                    return denied;
+                }
+                collection
+                    .insert_one_with_session(document, None, session)
+                    .await
+                    .map(|result| BulkOperationResult {
+                        index,
+                        ok: true,
+                        inserted_id: Some(result.inserted_id),
+                        ..Default::default()
+                    })
+            }
+            BulkOperation::UpdateOne { filter, update }
+            | BulkOperation::UpdateMany { filter, update } => {
+                let filter = match decision.restrict_filter(Some(filter)) {
+                    Some(filter) if decision.allows_document(&update, false) => filter,
+                    _ => return denied,
+                };
+                let update = UpdateModifications::Document(doc! {"$set": update});
+                match many {
+                    true => {
+                        collection
+                            .update_many_with_session(filter, update, None, session)
+                            .await
+                    }
+                    false => {
+                        collection
+                            .update_one_with_session(filter, update, None, session)
+                            .await
+                    }
+                }
+                .map(|result| updated(result.matched_count, result.modified_count))
+            }
+            BulkOperation::ReplaceOne {
+                filter,
+                replacement,
+            } => {
+                let filter = match decision.restrict_filter(Some(filter)) {
+                    Some(filter) if decision.allows_document(&replacement, true) => filter,
+                    _ => return denied,
+                };
+                collection
+                    .replace_one_with_session(filter, replacement, None, session)
+                    .await
+                    .map(|result| updated(result.matched_count, result.modified_count))
+            }
+            BulkOperation::DeleteOne { filter } => match decision.restrict_filter(Some(filter)) {
+                Some(filter) => collection
+                    .delete_one_with_session(filter, None, session)
+                    .await
+                    .map(|result| deleted(result.deleted_count)),
+                None => return denied,
+            },
+            BulkOperation::DeleteMany { filter } => match decision.restrict_filter(Some(filter)) {
+                Some(filter) => collection
+                    .delete_many_with_session(filter, None, session)
+                    .await
+                    .map(|result| deleted(result.deleted_count)),
+                None => return denied,
+            },
+        };
+        result
+            .unwrap_or_else(|error| BulkOperationResult::failed(index, write_error_message(&error)))
+    }
+
+    /// Runs inserts, updates, replacements and deletions in one request, one
+    /// after the other and each checked against the rules. Ordered requests
+    /// stop at the first failed operation. The writes are not atomic.
+    pub async fn bulk_write(
+        &self,
+        project_id: &str,
+        collection_name: &str,
+        caller: &RuleCaller,
+        operations: Vec<BulkOperation>,
+        ordered: bool,
+    ) -> SBResult<BulkWriteReport> {
+        ProjectMongoDBService::check_bulk_size(operations.len())?;
+        let count = operations.len();
+        let rules = self.get_caller_rules(project_id, caller).await?;
+        let collection = self
+            .get_database(project_id)
+            .collection::<Document>(collection_name);
+        let mut session = self.start_session().await?;
+        let mut results = vec![];
+        for (index, operation) in operations.into_iter().enumerate() {
+            let result = ProjectMongoDBService::run_bulk_operation(
+                &collection,
+                &rules,
+                caller,
+                &mut session,
+                index,
+                operation,
+            )
+            .await;
+            let failed = !result.ok;
+            results.push(result);
+            if failed && ordered {
+                break;
+            }
+        }
+        Ok(BulkWriteReport::new(results, count))
+    }
+
+    /// Runs operations on collections of the project database in one
+    /// transaction, rolled back at the first failed operation. Transactions
+    /// need a replica set.
+    pub async fn run_transaction(
+        &self,
+        project_id: &str,
+        caller: &RuleCaller,
+        operations: Vec<TransactionOperation>,
+    ) -> SBResult<BulkWriteReport> {
+        ProjectMongoDBService::check_bulk_size(operations.len())?;
+        let count = operations.len();
+        let rules = self.get_caller_rules(project_id, caller).await?;
+        let database = self.get_database(project_id);
+        let mut session = self.start_session().await?;
+        session
+            .start_transaction(None)
+            .await
+            .map_err(|error| match error.kind.as_ref() {
+                ErrorKind::Transaction { .. } => SBError::ServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Transactions need a replica set."),
+                },
+                _ => SBError::InternalServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Failure starting transaction."),
+                },
+            })?;
+
+        let mut results = vec![];
+        for (index, operation) in operations.into_iter().enumerate() {
+            let collection = database.collection::<Document>(&operation.collection);
+            let result = ProjectMongoDBService::run_bulk_operation(
+                &collection,
+                &rules,
+                caller,
+                &mut session,
+                index,
+                operation.operation,
+            )
+            .await;
+            let failed = !result.ok;
+            results.push(result);
+            if failed {
+                // The transaction is dropped by the server when aborting fails.
+                let _ = session.abort_transaction().await;
+                return Ok(BulkWriteReport::new(results, count));
+            }
+        }
+        session.commit_transaction().await.map_err(|error| {
+            match error.contains_label(TRANSIENT_TRANSACTION_ERROR) {
+                true => SBError::ServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Write conflict, try again."),
+                },
+                false => SBError::InternalServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Failure committing transaction."),
+                },
+            }
+        })?;
+        Ok(BulkWriteReport::new(results, count))
+    }
+
     pub async fn create_collection(
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         options: Option<CreateCollectionOptions>,
     ) -> SBResult<()> {
-        let database = self.client.database(&format!("project-{}", project_id));
+        ProjectMongoDBService::require_role(caller, ProjectRole::Developer)?;
+        if is_reserved_collection(collection_name) {
+            return Err(ProjectMongoDBService::permission_denied());
+        }
+        // Views read other collections without going through the rules.
+        if let Some(options) = &options {
+            if options.view_on.is_some() || options.pipeline.is_some() {
+                return Err(SBError::ServiceError {
+                    service: String::from("mongodb"),
+                    message: String::from("Views cannot be created."),
+                });
+            }
+        }
+        let database = self.get_database(project_id);
         database
             .create_collection(collection_name, options)
             .await
@@ -133,9 +852,14 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         options: Option<DropCollectionOptions>,
     ) -> SBResult<()> {
-        let database = self.client.database(&format!("project-{}", project_id));
+        ProjectMongoDBService::require_role(caller, ProjectRole::Developer)?;
+        if is_reserved_collection(collection_name) {
+            return Err(ProjectMongoDBService::permission_denied());
+        }
+        let database = self.get_database(project_id);
         database
             .collection::<Document>(collection_name)
             .drop(options)
@@ -150,10 +874,20 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         filter: Document,
         options: Option<DeleteOptions>,
     ) -> SBResult<DeleteResult> {
-        let database = self.client.database(&format!("project-{}", project_id));
+        let (_, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Delete,
+                caller,
+                Some(filter),
+            )
+            .await?;
+        let database = self.get_database(project_id);
         database
             .collection::<Document>(collection_name)
             .delete_many(filter, options)
@@ -168,6 +902,7 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         document_id: &str,
         options: Option<DeleteOptions>,
     ) -> SBResult<DeleteResult> {
@@ -175,19 +910,46 @@ impl ProjectMongoDBService {
             service: String::from("mongodb"),
             message: String::from("Failure making oid object."),
         })?;
-        self.delete_documents(project_id, collection_name, doc! {"_id": oid}, options)
-            .await
+        self.delete_documents(
+            project_id,
+            collection_name,
+            caller,
+            doc! {"_id": oid},
+            options,
+        )
+        .await
     }
 
     pub async fn update_documents(
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         filter: Document,
         update: Document,
         options: Option<UpdateOptions>,
     ) -> SBResult<UpdateResult> {
-        let database = self.client.database(&format!("project-{}", project_id));
+        let (decision, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Update,
+                caller,
+                Some(filter),
+            )
+            .await?;
+        if !decision.allows_document(&update, false) {
+            return Err(ProjectMongoDBService::permission_denied());
+        }
+        self.authorize_upsert(
+            project_id,
+            collection_name,
+            caller,
+            options.as_ref().and_then(|options| options.upsert),
+            &update,
+        )
+        .await?;
+        let database = self.get_database(project_id);
         database
             .collection::<Document>(collection_name)
             .update_many(
@@ -206,6 +968,7 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         document_id: &str,
         update: Document,
         options: Option<UpdateOptions>,
@@ -217,6 +980,7 @@ impl ProjectMongoDBService {
         self.update_documents(
             project_id,
             collection_name,
+            caller,
             doc! {"_id": oid},
             update,
             options,
@@ -228,6 +992,7 @@ impl ProjectMongoDBService {
         &self,
         project_id: &str,
         collection_name: &str,
+        caller: &RuleCaller,
         document_id: &str,
         set: Document,
         options: Option<ReplaceOptions>,
@@ -236,10 +1001,30 @@ impl ProjectMongoDBService {
             service: String::from("mongodb"),
             message: String::from("Failure making oid object."),
         })?;
-        let database = self.client.database(&format!("project-{}", project_id));
+        let (decision, filter) = self
+            .authorize_filter(
+                project_id,
+                collection_name,
+                RuleOperation::Update,
+                caller,
+                Some(doc! {"_id": oid}),
+            )
+            .await?;
+        if !decision.allows_document(&set, true) {
+            return Err(ProjectMongoDBService::permission_denied());
+        }
+        self.authorize_upsert(
+            project_id,
+            collection_name,
+            caller,
+            options.as_ref().and_then(|options| options.upsert),
+            &set,
+        )
+        .await?;
+        let database = self.get_database(project_id);
         database
             .collection::<Document>(collection_name)
-            .replace_one(doc! {"_id":oid}, set, options)
+            .replace_one(filter, set, options)
             .await
             .map_err(|_| SBError::InternalServiceError {
                 service: String::from("mongodb"),
@@ -247,3 +1032,95 @@ impl ProjectMongoDBService {
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_paginate() {
+        let (_, options, _, page_size) = ProjectMongoDBService::paginate(
+            doc! {},
+            Some(FindOptions::builder().projection(doc! {"name": 1}).build()),
+            None,
+            true,
+        )
+        .unwrap();
+        assert_eq!(page_size, Some(DEFAULT_PAGE_SIZE));
+        assert_eq!(options.limit, Some(DEFAULT_PAGE_SIZE + 1));
+
+        let skip = FindOptions::builder().skip(10).build();
+        assert!(ProjectMongoDBService::paginate(doc! {}, Some(skip.clone()), None, true).is_err());
+        assert!(ProjectMongoDBService::paginate(doc! {}, Some(skip), None, false).is_ok());
+
+        let projection = FindOptions::builder()
+            .sort(doc! {"rank": 1})
+            .projection(doc! {"name": 1})
+            .build();
+        assert!(ProjectMongoDBService::paginate(doc! {}, Some(projection), None, true).is_err());
+    }
+
+    fn insert_into(collection: &str, document: Document) -> TransactionOperation {
+        TransactionOperation {
+            collection: String::from(collection),
+            operation: BulkOperation::InsertOne { document },
+        }
+    }
+
+    async fn count(database: &Database, collection: &str) -> u64 {
+        database
+            .collection::<Document>(collection)
+            .count_documents(doc! {}, None)
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs MongoDB on localhost:27017 running as a replica set"]
+    async fn test_transaction_commits_or_rolls_back() {
+        let client = Client::with_uri_str("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let service = ProjectMongoDBService::new(client);
+        let project_id = ObjectId::new().to_hex();
+        let caller = RuleCaller::Member(ProjectRole::Developer);
+        let database = service.get_database(&project_id);
+
+        let report = service
+            .run_transaction(
+                &project_id,
+                &caller,
+                vec![
+                    insert_into("orders", doc! {"_id": 1}),
+                    insert_into("stock", doc! {"_id": 1}),
+                ],
+            )
+            .await
+            .unwrap();
+        assert!(report.ok);
+        assert_eq!(report.skipped, 0);
+        assert_eq!(count(&database, "orders").await, 1);
+        assert_eq!(count(&database, "stock").await, 1);
+
+        let report = service
+            .run_transaction(
+                &project_id,
+                &caller,
+                vec![
+                    insert_into("orders", doc! {"_id": 2}),
+                    insert_into("stock", doc! {"_id": 1}),
+                    insert_into("stock", doc! {"_id": 3}),
+                ],
+            )
+            .await
+            .unwrap();
+        assert!(!report.ok);
+        assert_eq!(report.skipped, 1);
+        assert!(report.results[0].ok);
+        assert!(!report.results[1].ok);
+        assert_eq!(count(&database, "orders").await, 1);
+        assert_eq!(count(&database, "stock").await, 1);
+
+        database.drop(None).await.unwrap();
+    }
+}
@@ -1,8 +1,15 @@
-use crate::models::project::Project;
+use crate::models::api_key::{
+    display_prefix, generate_api_key, hash_api_key, ApiKeyKind, CreatedApiKey, ProjectApiKey,
+};
+use crate::models::project::{
+    Project, ProjectInvitation, ProjectInvitationSummary, ProjectRole, ProjectUpdate,
+};
+use auth::models::oidc::OidcProvider;
 use error::{SBError, SBResult};
 use futures::TryStreamExt;
-use mongodb::bson::doc;
 use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{doc, to_bson, Bson, DateTime, Document};
+use mongodb::options::{UpdateModifications, UpdateOptions};
 use mongodb::results::InsertOneResult;
 use mongodb::Collection;
 use serde::{Deserialize, Serialize};
@@ -23,13 +30,20 @@ impl ProjectService {
         ProjectService { collection }
     }
 
+    fn member_filter(user_id: &str) -> Document {
+        doc! {"$or": [{"users.userId": user_id}, {"users": user_id}]}
+    }
+
+    /// Excludes the projects waiting to be purged after a soft delete.
+    fn active_filter(filter: Document) -> Document {
+        doc! {"$and": [filter, {"deletedAt": null}]}
+    }
+
     pub async fn get_projects_for_user(&self, user_id: &str) -> SBResult<Vec<Project>> {
         let cursor = self
             .collection
             .find(
-                doc! {
-                    "users": user_id
-                },
+                ProjectService::active_filter(ProjectService::member_filter(user_id)),
                 None,
             )
             .await
@@ -47,19 +61,25 @@ impl ProjectService {
     }
 
     pub async fn get(&self, project_id: &str) -> SBResult<Project> {
+        self.find(project_id, false).await
+    }
+
+    async fn find(&self, project_id: &str, include_deleted: bool) -> SBResult<Project> {
         let project_oid =
             ObjectId::from_str(project_id).map_err(|_| SBError::InternalServiceError {
                 service: String::from("projects"),
                 message: String::from("Failure making oid object."),
             })?;
-        let res = self
-            .collection
-            .find_one(doc! {"_id":project_oid}, None)
-            .await
-            .map_err(|_| SBError::InternalServiceError {
+        let filter = match include_deleted {
+            true => doc! {"_id": project_oid},
+            false => ProjectService::active_filter(doc! {"_id": project_oid}),
+        };
+        let res = self.collection.find_one(filter, None).await.map_err(|_| {
+            SBError::InternalServiceError {
                 service: String::from("projects"),
                 message: String::from("Failure finding project."),
-            })?;
+            }
+        })?;
         match res {
             Some(r) => Ok(r),
             None => Err(SBError::ServiceError {
@@ -94,7 +114,12 @@ impl ProjectService {
             })?;
         let res = self
             .collection
-            .find_one(doc! {"_id":project_oid, "users": user_id}, None)
+            .find_one(
+                ProjectService::active_filter(
+                    doc! {"$and": [{"_id": project_oid}, ProjectService::member_filter(user_id)]},
+                ),
+                None,
+            )
             .await
             .map_err(|_| SBError::InternalServiceError {
                 service: String::from("projects"),
@@ -102,4 +127,697 @@ impl ProjectService {
             })?;
         Ok(res)
     }
+
+    pub async fn get_invitations_for_user(
+        &self,
+        user_id: &str,
+    ) -> SBResult<Vec<ProjectInvitationSummary>> {
+        let cursor = self
+            .collection
+            .find(
+                ProjectService::active_filter(doc! {"invitations.userId": user_id}),
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure listing invitations."),
+            })?;
+        let projects = cursor.try_collect::<Vec<Project>>().await.map_err(|_| {
+            SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure listing invitations."),
+            }
+        })?;
+        Ok(projects
+            .iter()
+            .filter_map(|project| {
+                project
+                    .get_invitation(user_id)
+                    .map(|invitation| ProjectInvitationSummary {
+                        project_id: project.id.map(|id| id.to_hex()).unwrap_or_default(),
+                        project_name: project.name.clone(),
+                        role: invitation.role,
+                    })
+            })
+            .collect())
+    }
+
+    pub async fn invite_user(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        role: ProjectRole,
+    ) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        if project.get_role(user_id).is_some() || project.get_invitation(user_id).is_some() {
+            return Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("User already member or invited."),
+            });
+        }
+        let invitation = ProjectInvitation {
+            user_id: String::from(user_id),
+            role,
+        };
+        let invitation = to_bson(&invitation).map_err(|_| SBError::InternalServiceError {
+            service: String::from("projects"),
+            message: String::from("Failure serializing invitation."),
+        })?;
+        let invited = self
+            .update_members(
+                &project,
+                vec![
+                    doc! {"users.userId": {"$ne": user_id}},
+                    doc! {"invitations.userId": {"$ne": user_id}},
+                ],
+                doc! {"$push": {"invitations": invitation}},
+                None,
+            )
+            .await?;
+        match invited {
+            true => Ok(()),
+            false => Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("User already member or invited."),
+            }),
+        }
+    }
+
+    pub async fn accept_invitation(&self, project_id: &str, user_id: &str) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        let invitation = match project.get_invitation(user_id) {
+            Some(invitation) => invitation.clone(),
+            None => return Err(ProjectService::no_invitation()),
+        };
+        let role = ProjectService::role_to_bson(invitation.role)?;
+        let accepted = self
+            .update_members(
+                &project,
+                vec![
+                    doc! {"invitations": {"$elemMatch": {"userId": user_id, "role": role.clone()}}},
+                    doc! {"users.userId": {"$ne": user_id}},
+                ],
+                doc! {
+                    "$pull": {"invitations": {"userId": user_id}},
+                    "$push": {"users": {"userId": user_id, "role": role}},
+                },
+                None,
+            )
+            .await?;
+        match accepted {
+            true => Ok(()),
+            false => Err(ProjectService::no_invitation()),
+        }
+    }
+
+    pub async fn decline_invitation(&self, project_id: &str, user_id: &str) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        let declined = self
+            .update_members(
+                &project,
+                vec![doc! {"invitations.userId": user_id}],
+                doc! {"$pull": {"invitations": {"userId": user_id}}},
+                None,
+            )
+            .await?;
+        match declined {
+            true => Ok(()),
+            false => Err(ProjectService::no_invitation()),
+        }
+    }
+
+    pub async fn set_member_role(
+        &self,
+        project_id: &str,
+        user_id: &str,
+        role: ProjectRole,
+    ) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        ProjectService::check_member_change(&project, user_id, Some(role))?;
+        let mut conditions = vec![doc! {"users.userId": user_id}];
+        if role != ProjectRole::Owner {
+            conditions.push(ProjectService::other_owner_filter(user_id));
+        }
+        let options = UpdateOptions::builder()
+            .array_filters(vec![doc! {"member.userId": user_id}])
+            .build();
+        let updated = self
+            .update_members(
+                &project,
+                conditions,
+                doc! {"$set": {"users.$[member].role": ProjectService::role_to_bson(role)?}},
+                options,
+            )
+            .await?;
+        match updated {
+            true => Ok(()),
+            false => ProjectService::check_member_change(
+                &self.get(project_id).await?,
+                user_id,
+                Some(role),
+            ),
+        }
+    }
+
+    pub async fn remove_member(&self, project_id: &str, user_id: &str) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        ProjectService::check_member_change(&project, user_id, None)?;
+        let removed = self
+            .update_members(
+                &project,
+                vec![
+                    doc! {"users.userId": user_id},
+                    ProjectService::other_owner_filter(user_id),
+                ],
+                doc! {"$pull": {"users": {"userId": user_id}}},
+                None,
+            )
+            .await?;
+        match removed {
+            true => Ok(()),
+            false => {
+                ProjectService::check_member_change(&self.get(project_id).await?, user_id, None)
+            }
+        }
+    }
+
+    fn no_invitation() -> SBError {
+        SBError::ServiceError {
+            service: String::from("projects"),
+            message: String::from("No invitation found"),
+        }
+    }
+
+    fn role_to_bson(role: ProjectRole) -> SBResult<Bson> {
+        to_bson(&role).map_err(|_| SBError::InternalServiceError {
+            service: String::from("projects"),
+            message: String::from("Failure serializing members."),
+        })
+    }
+
+    /// Refuses to change the role of, or remove when `new_role` is `None`, a
+    /// user who is not a member or the last owner.
+    fn check_member_change(
+        project: &Project,
+        user_id: &str,
+        new_role: Option<ProjectRole>,
+    ) -> SBResult<()> {
+        if project.get_role(user_id).is_none() {
+            return Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("No member found"),
+            });
+        }
+        if project.would_remove_last_owner(user_id, new_role) {
+            return Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("A project needs at least one owner."),
+            });
+        }
+        Ok(())
+    }
+
+    /// Matches projects with an owner other than `user_id`, so updates of
+    /// that user never leave a project without owner.
+    fn other_owner_filter(user_id: &str) -> Document {
+        doc! {"users": {"$elemMatch": {"userId": {"$ne": user_id}, "role": "owner"}}}
+    }
+
+    /// Applies an update to the members or invitations of a project when it
+    /// still matches `conditions`, returning whether it did. Each update is a
+    /// single atomic write so concurrent changes are not lost.
+    async fn update_members(
+        &self,
+        project: &Project,
+        conditions: Vec<Document>,
+        update: Document,
+        options: impl Into<Option<UpdateOptions>>,
+    ) -> SBResult<bool> {
+        self.migrate_members(project).await?;
+        let mut filter = vec![doc! {"_id": project.id}];
+        filter.extend(conditions);
+        self.collection
+            .update_one(doc! {"$and": filter}, update, options)
+            .await
+            .map(|result| result.matched_count > 0)
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure updating members."),
+            })
+    }
+
+    /// Rewrites legacy member entries, the plain ids of owners, to the role
+    /// format the member updates match on.
+    async fn migrate_members(&self, project: &Project) -> SBResult<()> {
+        let pipeline = vec![doc! {"$set": {"users": {"$map": {
+            "input": "$users",
+            "in": {"$cond": [
+                {"$eq": [{"$type": "$$this"}, "string"]},
+                {"userId": "$$this", "role": "owner"},
+                "$$this",
+            ]},
+        }}}}];
+        self.collection
+            .update_one(
+                doc! {"_id": project.id, "users": {"$type": "string"}},
+                UpdateModifications::Pipeline(pipeline),
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure updating members."),
+            })
+    }
+
+    pub async fn update(&self, project_id: &str, updates: ProjectUpdate) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        let mut updates_doc = Document::new();
+
+        if let Some(name) = updates.name {
+            updates_doc.insert("name", name);
+        }
+
+        if let Some(description) = updates.description {
+            updates_doc.insert("description", description);
+        }
+
+        if let Some(settings) = updates.settings {
+            updates_doc.insert("settings", settings);
+        }
+
+        if updates_doc.is_empty() {
+            return Ok(());
+        }
+
+        self.collection
+            .update_one(doc! {"_id": project.id}, doc! {"$set": updates_doc}, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure updating project."),
+            })
+    }
+
+    pub async fn soft_delete(&self, project_id: &str) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        self.collection
+            .update_one(
+                doc! {"_id": project.id},
+                doc! {"$set": {"deletedAt": DateTime::now()}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure deleting project."),
+            })
+    }
+
+    pub async fn restore(&self, project_id: &str, user_id: &str) -> SBResult<()> {
+        let project = self.find(project_id, true).await?;
+        if project.get_role(user_id) != Some(ProjectRole::Owner) {
+            return Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("Only owners can restore a project."),
+            });
+        }
+        if project.deleted_at.is_none() {
+            return Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("Project is not deleted."),
+            });
+        }
+        // Only restores a project the purge has not claimed meanwhile.
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": project.id, "deletedAt": {"$ne": null}},
+                doc! {"$unset": {"deletedAt": ""}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure restoring project."),
+            })?;
+        match result.matched_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("No project found"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Marks a project deleted, keeping the date of an earlier soft delete.
+    /// Deleted projects take no more writes, and are left to the purge when
+    /// their tenant database cannot be dropped.
+    pub async fn mark_deleted(&self, project_id: &str) -> SBResult<()> {
+        let project = self.find(project_id, true).await?;
+        self.collection
+            .update_one(
+                doc! {"_id": project.id},
+                doc! {"$min": {"deletedAt": DateTime::now()}},
+                None,
+            )
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure deleting project."),
+            })
+    }
+
+    /// Removes the project document, the tenant database is dropped first by
+    /// `ProjectMongoDBService::drop_project_database`.
+    pub async fn delete(&self, project_id: &str) -> SBResult<()> {
+        let project = self.find(project_id, true).await?;
+        self.collection
+            .delete_one(doc! {"_id": project.id}, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure deleting project."),
+            })
+    }
+
+    /// Removes the project document if it is still soft deleted since
+    /// `deleted_before`, returning whether it was. The purge claims a project
+    /// this way before dropping its tenant database, so a concurrent restore
+    /// either wins or finds no project.
+    pub async fn purge(&self, project_id: &str, deleted_before: DateTime) -> SBResult<bool> {
+        let project_oid =
+            ObjectId::from_str(project_id).map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure making oid object."),
+            })?;
+        self.collection
+            .find_one_and_delete(
+                doc! {"_id": project_oid, "deletedAt": {"$lte": deleted_before}},
+                None,
+            )
+            .await
+            .map(|project| project.is_some())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure deleting project."),
+            })
+    }
+
+    /// Whether the project exists and is not soft deleted.
+    pub async fn is_active(&self, project_id: &str) -> SBResult<bool> {
+        match self.get(project_id).await {
+            Ok(_) => Ok(true),
+            Err(SBError::ServiceError { .. }) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub async fn get_projects_deleted_before(&self, date: DateTime) -> SBResult<Vec<Project>> {
+        let cursor = self
+            .collection
+            .find(doc! {"deletedAt": {"$lte": date}}, None)
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure listing projects."),
+            })?;
+        cursor
+            .try_collect::<Vec<Project>>()
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure listing projects."),
+            })
+    }
+
+    pub async fn get_api_key(
+        &self,
+        project_id: &str,
+        key: &str,
+    ) -> SBResult<Option<ProjectApiKey>> {
+        let hash = hash_api_key(key);
+        let project_oid =
+            ObjectId::from_str(project_id).map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure making oid object."),
+            })?;
+        let res = self
+            .collection
+            .find_one(
+                ProjectService::active_filter(doc! {"_id": project_oid, "apiKeys.hash": &hash}),
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure finding project."),
+            })?;
+        Ok(res.and_then(|project| {
+            project
+                .api_keys
+                .into_iter()
+                .find(|api_key| api_key.hash == hash)
+        }))
+    }
+
+    pub async fn create_api_key(
+        &self,
+        project_id: &str,
+        name: &str,
+        kind: ApiKeyKind,
+    ) -> SBResult<CreatedApiKey> {
+        let project = self.get(project_id).await?;
+        let key = generate_api_key(kind);
+        let api_key = ProjectApiKey {
+            id: ObjectId::new().to_hex(),
+            name: String::from(name),
+            kind,
+            prefix: display_prefix(&key),
+            hash: hash_api_key(&key),
+            created_at: DateTime::now(),
+        };
+        let kind = to_bson(&kind).map_err(|_| SBError::InternalServiceError {
+            service: String::from("projects"),
+            message: String::from("Failure serializing api key."),
+        })?;
+        self.collection
+            .update_one(
+                doc! {"_id": project.id},
+                doc! {"$push": {"apiKeys": {
+                    "id": &api_key.id,
+                    "name": &api_key.name,
+                    "kind": kind,
+                    "prefix": &api_key.prefix,
+                    "hash": &api_key.hash,
+                    "createdAt": api_key.created_at,
+                }}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure creating api key."),
+            })?;
+        Ok(CreatedApiKey { key, api_key })
+    }
+
+    pub async fn revoke_api_key(&self, project_id: &str, key_id: &str) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": project.id},
+                doc! {"$pull": {"apiKeys": {"id": key_id}}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure revoking api key."),
+            })?;
+        match result.modified_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("No api key found"),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Replaces the secret of an existing key, the previous value stops
+    /// working immediately.
+    pub async fn rotate_api_key(&self, project_id: &str, key_id: &str) -> SBResult<CreatedApiKey> {
+        let project = self.get(project_id).await?;
+        let mut api_key = match project.api_keys.iter().find(|api_key| api_key.id == key_id) {
+            Some(api_key) => api_key.clone(),
+            None => {
+                return Err(SBError::ServiceError {
+                    service: String::from("projects"),
+                    message: String::from("No api key found"),
+                })
+            }
+        };
+        let key = generate_api_key(api_key.kind);
+        api_key.prefix = display_prefix(&key);
+        api_key.hash = hash_api_key(&key);
+        self.collection
+            .update_one(
+                doc! {"_id": project.id, "apiKeys.id": key_id},
+                doc! {"$set": {"apiKeys.$.prefix": &api_key.prefix, "apiKeys.$.hash": &api_key.hash}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure rotating api key."),
+            })?;
+        Ok(CreatedApiKey { key, api_key })
+    }
+
+    /// Adds an identity provider or replaces the one with the same name. The
+    /// client secret is kept when the new configuration has none.
+    pub async fn set_auth_provider(
+        &self,
+        project_id: &str,
+        provider: OidcProvider,
+    ) -> SBResult<()> {
+        let valid_name = !provider.name.is_empty()
+            && provider
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name {
+            return Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("Invalid provider name."),
+            });
+        }
+        let project = self.get(project_id).await?;
+        let existing = project.get_auth_provider(&provider.name);
+        let client_secret = provider
+            .client_secret
+            .clone()
+            .or_else(|| existing.and_then(|existing| existing.client_secret.clone()));
+        let mut stored = to_bson(&provider)
+            .ok()
+            .and_then(|stored| stored.as_document().cloned())
+            .ok_or_else(|| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure serializing auth provider."),
+            })?;
+        if let Some(client_secret) = client_secret {
+            stored.insert("clientSecret", client_secret);
+        }
+        let (filter, update) = match existing {
+            Some(_) => (
+                doc! {"_id": project.id, "authProviders.name": &provider.name},
+                doc! {"$set": {"authProviders.$": stored}},
+            ),
+            None => (
+                doc! {"_id": project.id},
+                doc! {"$push": {"authProviders": stored}},
+            ),
+        };
+        self.collection
+            .update_one(filter, update, None)
+            .await
+            .map(|_| ())
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure saving auth provider."),
+            })
+    }
+
+    pub async fn remove_auth_provider(&self, project_id: &str, name: &str) -> SBResult<()> {
+        let project = self.get(project_id).await?;
+        let result = self
+            .collection
+            .update_one(
+                doc! {"_id": project.id},
+                doc! {"$pull": {"authProviders": {"name": name}}},
+                None,
+            )
+            .await
+            .map_err(|_| SBError::InternalServiceError {
+                service: String::from("projects"),
+                message: String::from("Failure removing auth provider."),
+            })?;
+        match result.modified_count {
+            0 => Err(SBError::ServiceError {
+                service: String::from("projects"),
+                message: String::from("No auth provider found"),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mongodb::Client;
+
+    const DB_URL: &str = "mongodb://localhost:27017";
+    const DB_NAME: &str = "snellbaas_test";
+
+    #[actix_rt::test]
+    #[ignore = "needs MongoDB on localhost:27017"]
+    async fn test_concurrent_member_changes_keep_an_owner() {
+        let client = Client::with_uri_str(DB_URL).await.unwrap();
+        let collection = client
+            .database(DB_NAME)
+            .collection::<Project>(&format!("projects_{}", ObjectId::new()));
+        let raw = collection.clone_with_type::<Document>();
+        let project_id = raw
+            .insert_one(
+                doc! {
+                    "name": "test",
+                    "users": ["legacy", {"userId": "owner", "role": "owner"}],
+                    "invitations": [],
+                },
+                None,
+            )
+            .await
+            .unwrap()
+            .inserted_id
+            .as_object_id()
+            .unwrap()
+            .to_hex();
+        let service = ProjectService::new(collection.clone());
+
+        let (removed, demoted) = futures::join!(
+            service.remove_member(&project_id, "legacy"),
+            service.set_member_role(&project_id, "owner", ProjectRole::Viewer),
+        );
+        assert!(removed.is_ok() != demoted.is_ok());
+        let project = service.get(&project_id).await.unwrap();
+        assert!(project
+            .users
+            .iter()
+            .any(|member| member.role == ProjectRole::Owner));
+
+        service
+            .invite_user(&project_id, "invited", ProjectRole::Developer)
+            .await
+            .unwrap();
+        service
+            .accept_invitation(&project_id, "invited")
+            .await
+            .unwrap();
+        assert!(service
+            .accept_invitation(&project_id, "invited")
+            .await
+            .is_err());
+        let project = service.get(&project_id).await.unwrap();
+        assert_eq!(project.get_role("invited"), Some(ProjectRole::Developer));
+        assert!(project.invitations.is_empty());
+        collection.drop(None).await.unwrap();
+    }
 }
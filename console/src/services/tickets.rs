@@ -0,0 +1,79 @@
+use crate::models::project::Credentials;
+use rand::RngCore;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Time a ticket can be redeemed in.
+pub const TICKET_TTL: Duration = Duration::from_secs(30);
+
+/// Short lived, single use tickets opening a realtime subscription for
+/// clients that cannot set headers, like browser WebSockets and
+/// EventSources. A ticket stands for the credentials it was issued for, so
+/// tokens and API keys stay out of URLs. Clones share the tickets.
+#[derive(Clone, Default)]
+pub struct TicketStore {
+    tickets: Arc<Mutex<HashMap<String, IssuedTicket>>>,
+}
+
+struct IssuedTicket {
+    issued_at: Instant,
+    project_id: String,
+    credentials: Credentials,
+}
+
+impl TicketStore {
+    pub fn new() -> TicketStore {
+        TicketStore::default()
+    }
+
+    pub fn issue(&self, project_id: &str, credentials: Credentials) -> String {
+        let mut bytes = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let ticket = hex::encode(bytes);
+        if let Ok(mut tickets) = self.tickets.lock() {
+            tickets.retain(|_, issued| issued.issued_at.elapsed() < TICKET_TTL);
+            tickets.insert(
+                ticket.clone(),
+                IssuedTicket {
+                    issued_at: Instant::now(),
+                    project_id: String::from(project_id),
+                    credentials,
+                },
+            );
+        }
+        ticket
+    }
+
+    /// Takes the credentials of a ticket issued for `project_id`, a ticket
+    /// is gone once redeemed.
+    pub fn redeem(&self, project_id: &str, ticket: &str) -> Option<Credentials> {
+        let issued = self.tickets.lock().ok()?.remove(ticket)?;
+        match issued.issued_at.elapsed() < TICKET_TTL && issued.project_id == project_id {
+            true => Some(issued.credentials),
+            false => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tickets() {
+        let store = TicketStore::new();
+        let credentials = Credentials {
+            authorization: Some(String::from("Bearer token")),
+            api_key: None,
+        };
+        let ticket = store.issue("project", credentials.clone());
+        assert!(store.redeem("other", &ticket).is_none());
+
+        let ticket = store.issue("project", credentials);
+        let redeemed = store.clone().redeem("project", &ticket).unwrap();
+        assert_eq!(redeemed.authorization.as_deref(), Some("Bearer token"));
+        assert!(store.redeem("project", &ticket).is_none());
+        assert!(store.redeem("project", "unknown").is_none());
+    }
+}
@@ -0,0 +1,146 @@
+use crate::services::project_mongodb::ProjectMongoDBService;
+use crate::services::projects::ProjectService;
+use actix_web::rt::time;
+use mongodb::bson::DateTime;
+use std::time::Duration;
+
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Permanently removes the projects soft deleted for longer than
+/// `grace_period`, including their `project-{id}` database.
+pub async fn purge_deleted_projects(
+    project_service: ProjectService,
+    project_mongodb_service: ProjectMongoDBService,
+    grace_period: Duration,
+) {
+    let mut interval = time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        let deadline = DateTime::from_millis(
+            DateTime::now().timestamp_millis() - grace_period.as_millis() as i64,
+        );
+        purge_projects_deleted_before(&project_service, &project_mongodb_service, deadline).await;
+    }
+}
+
+/// Purges the projects soft deleted before `deadline`, returning how many
+/// were removed.
+pub async fn purge_projects_deleted_before(
+    project_service: &ProjectService,
+    project_mongodb_service: &ProjectMongoDBService,
+    deadline: DateTime,
+) -> usize {
+    let projects = match project_service.get_projects_deleted_before(deadline).await {
+        Ok(projects) => projects,
+        Err(error) => {
+            println!("{}", error);
+            return 0;
+        }
+    };
+    let mut purged = 0;
+    for project in projects {
+        let project_id = match project.id {
+            Some(id) => id.to_hex(),
+            None => continue,
+        };
+        // Claiming the project first keeps a restore made since the listing
+        // from losing its database.
+        let result = match project_service.purge(&project_id, deadline).await {
+            Ok(true) => project_mongodb_service
+                .drop_project_database(&project_id)
+                .await
+                .map(|_| purged += 1),
+            Ok(false) => Ok(()),
+            Err(error) => Err(error),
+        };
+        if let Err(error) = result {
+            println!("{}", error);
+        }
+    }
+    purged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::project::{Project, ProjectMember, ProjectRole};
+    use mongodb::bson::{oid::ObjectId, Document};
+    use mongodb::{Client, Collection};
+
+    const DB_URL: &str = "mongodb://localhost:27017";
+    const DB_NAME: &str = "snellbaas_test";
+
+    async fn get_services() -> (ProjectService, ProjectMongoDBService, Collection<Project>) {
+        let client = Client::with_uri_str(DB_URL).await.unwrap();
+        let collection = client
+            .database(DB_NAME)
+            .collection::<Project>(&format!("projects_{}", ObjectId::new()));
+        (
+            ProjectService::new(collection.clone()),
+            ProjectMongoDBService::new(client),
+            collection,
+        )
+    }
+
+    async fn create_project(service: &ProjectService) -> String {
+        let project = Project {
+            id: None,
+            name: String::from("test"),
+            description: None,
+            settings: Document::new(),
+            users: vec![ProjectMember {
+                user_id: String::from("owner"),
+                role: ProjectRole::Owner,
+            }],
+            invitations: vec![],
+            deleted_at: None,
+            api_keys: vec![],
+            auth_providers: vec![],
+        };
+        service.create(project).await.unwrap()._id
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs MongoDB on localhost:27017"]
+    async fn test_soft_delete_and_restore() {
+        let (service, _, collection) = get_services().await;
+        let project_id = create_project(&service).await;
+        service.soft_delete(&project_id).await.unwrap();
+        assert!(!service.is_active(&project_id).await.unwrap());
+        assert!(service
+            .get_user_access_to_project(&project_id, "owner")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(service.restore(&project_id, "other").await.is_err());
+        service.restore(&project_id, "owner").await.unwrap();
+        assert!(service.is_active(&project_id).await.unwrap());
+        assert!(service.restore(&project_id, "owner").await.is_err());
+        collection.drop(None).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    #[ignore = "needs MongoDB on localhost:27017"]
+    async fn test_purge_skips_restored_projects() {
+        let (service, mongodb_service, collection) = get_services().await;
+        let deleted = create_project(&service).await;
+        let restored = create_project(&service).await;
+        service.soft_delete(&deleted).await.unwrap();
+        service.soft_delete(&restored).await.unwrap();
+        let deadline = DateTime::now();
+        service.restore(&restored, "owner").await.unwrap();
+
+        assert!(!service
+            .purge(&deleted, DateTime::from_millis(0))
+            .await
+            .unwrap());
+        assert!(!service.purge(&restored, deadline).await.unwrap());
+        assert_eq!(
+            purge_projects_deleted_before(&service, &mongodb_service, deadline).await,
+            1
+        );
+        assert!(service.restore(&deleted, "owner").await.is_err());
+        assert!(service.is_active(&restored).await.unwrap());
+        collection.drop(None).await.unwrap();
+    }
+}
@@ -5,6 +5,16 @@ export async function loginWithCredentials(email: string, password: string) {
 	return res;
 }
 
+export async function refreshSession(refreshToken: string) {
+	const res = await getClient().post('/auth/refresh', { refreshToken });
+	return res;
+}
+
+export async function logout() {
+	const res = await getClient().post('/auth/logout');
+	return res;
+}
+
 export async function register(info: IRegisterUser) {
 	const res = await getClient().post('/auth/signup', info);
 	return res;
@@ -14,3 +24,13 @@ export async function getProfile(): Promise<IUser> {
 	const res = await getClient().get('/auth/profile');
 	return res.data;
 }
+
+export async function changePassword(currentPassword: string, newPassword: string) {
+	const res = await getClient().post('/auth/password/change', { currentPassword, newPassword });
+	return res;
+}
+
+export async function verifyMfa(mfaToken: string, code: string) {
+	const res = await getClient().post('/auth/mfa/verify', { mfaToken, code });
+	return res;
+}
@@ -3,6 +3,7 @@ import type {
 	IMongoDBDocument,
 	IMongoDBDocumentCreated,
 	IMongoDBDocumentDeleted,
+	IMongoDBDocumentPage,
 	IMongoDBDocumentUpdated,
 } from '$lib/models/mongodb';
 import { getClient } from './client';
@@ -42,12 +43,13 @@ export async function getDocuments<T extends IMongoDBDocument>(
 	collectionName: string,
 	filter?: object,
 	options?: object,
-): Promise<T[]> {
+	cursor?: string,
+): Promise<IMongoDBDocumentPage<T>> {
 	const res = await getClient().post(
 		`/projects/services/${encodeURIComponent(projectId)}/mongodb/collections/${encodeURIComponent(
 			collectionName,
 		)}/documents`,
-		{ filter, options },
+		{ filter, options, cursor },
 	);
 	return res.data;
 }
@@ -37,3 +37,8 @@ interface _IMongoDBDocument {
 }
 
 export type IMongoDBDocument = _IMongoDBDocument & object;
+
+export interface IMongoDBDocumentPage<T extends IMongoDBDocument> {
+	documents: T[];
+	nextCursor: string | null;
+}
@@ -1,7 +1,22 @@
 import type { IMongoID } from './id';
 
+export type ProjectRole = 'viewer' | 'developer' | 'admin' | 'owner';
+
+export interface IProjectMember {
+	userId: string;
+	role: ProjectRole;
+}
+
+export interface IProjectInvitation {
+	userId: string;
+	role: ProjectRole;
+}
+
 export interface IProject {
 	_id: IMongoID;
 	name: string;
-	users: string[];
+	description?: string;
+	settings: Record<string, unknown>;
+	users: IProjectMember[];
+	invitations: IProjectInvitation[];
 }
@@ -11,5 +11,6 @@ export interface IUser {
 	firstName: string;
 	lastName: string;
 	email: string;
+	emailVerified: boolean;
 	id: string;
 }
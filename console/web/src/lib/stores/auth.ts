@@ -2,12 +2,21 @@ import * as auth from '$lib/api/auth';
 import { setAuthToken } from '$lib/api/client';
 import type { IRegisterUser, IUser } from '$lib/models/user';
 import { savedWritable } from '$lib/utils/stores';
+import { get } from 'svelte/store';
 
 const CURRENT_USER_TOKEN_KEY = 'current_token_user';
+const CURRENT_USER_REFRESH_TOKEN_KEY = 'current_refresh_token_user';
 const CURRENT_USER_KEY = 'current_user';
+const REFRESH_INTERVAL = 10 * 60 * 1000;
 
 export const currentUserToken = savedWritable<string>(CURRENT_USER_TOKEN_KEY);
 currentUserToken.subscribe(setAuthToken);
+export const currentUserRefreshToken = savedWritable<string>(CURRENT_USER_REFRESH_TOKEN_KEY);
+
+if (typeof window !== 'undefined') {
+	refreshSession();
+	setInterval(refreshSession, REFRESH_INTERVAL);
+}
 export const currentUser = savedWritable<IUser>(CURRENT_USER_KEY, null, (set) => {
 	const unsubscribe = currentUserToken.subscribe((token) => {
 		if (!token) {
@@ -30,15 +39,34 @@ export async function loginWithCredentials(email: string, password: string) {
 	const res = await auth.loginWithCredentials(email, password);
 	const data = res.data;
 	if (!data.success || !data.token) throw Error('Server denied request');
+	currentUserRefreshToken.set(data.refreshToken);
 	currentUserToken.set(data.token);
 	return data;
 }
 
+export async function refreshSession() {
+	const refreshToken = get(currentUserRefreshToken);
+	if (!refreshToken) return;
+	try {
+		const res = await auth.refreshSession(refreshToken);
+		currentUserRefreshToken.set(res.data.refreshToken);
+		currentUserToken.set(res.data.token);
+	} catch (_) {
+		currentUserRefreshToken.set(null);
+		currentUserToken.set(null);
+	}
+}
+
 export async function register(info: IRegisterUser) {
 	await auth.register(info);
 	return loginWithCredentials(info.email, info.password);
 }
 
 export async function logout() {
-	currentUserToken.set(null);
+	try {
+		await auth.logout();
+	} finally {
+		currentUserRefreshToken.set(null);
+		currentUserToken.set(null);
+	}
 }
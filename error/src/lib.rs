@@ -8,6 +8,11 @@ pub enum SBError {
     ServiceError { message: String, service: String },
     #[error("Internal service error [{service:?}]: {message:?}")]
     InternalServiceError { message: String, service: String },
+    #[error("Validation error [{service:?}]: {errors:?}")]
+    ValidationError {
+        errors: Vec<String>,
+        service: String,
+    },
     #[error("ENV Key Missing: {key:?}")]
     EnvConfigError { key: String },
 }